@@ -1,11 +1,12 @@
-use std::str::FromStr;
 use std::{fs, path::Path};
 
+use comal::config::manifest::{DataDir, TensorFile};
 use comal::config::Data;
 use comal::templates::accumulator::{MaxReduce, Reduce, ReduceData, Spacc1, Spacc1Data};
 use comal::templates::alu::{make_alu, make_unary_alu};
 use comal::templates::array::{Array, ArrayData};
 use comal::templates::crd_manager::{CrdDrop, CrdManagerData};
+use comal::templates::data_file::DataElement;
 use comal::templates::joiner::{CrdJoinerData, Intersect};
 use comal::templates::primitive::{ALUExpOp, Exp};
 use comal::templates::rd_scanner::{CompressedCrdRdScan, RdScanData};
@@ -58,41 +59,41 @@ struct TestData<ValType> {
     v_vals: Vec<ValType>,
 }
 
-fn load_data<ValType: FromStr + DAMType>(test_name: &str) -> TestData<ValType> {
+fn load_data<ValType: DataElement>(test_name: &str) -> TestData<ValType> {
     let filename = home::home_dir().unwrap().join("sam_config.toml");
     let contents = fs::read_to_string(filename).unwrap();
     let data: Data = toml::from_str(&contents).unwrap();
     let formatted_dir = data.sam_config.sam_path;
-    let base_path = Path::new(&formatted_dir).join(test_name);
-    let q0_seg_filename = base_path.join("tensor_Q_mode_0_seg");
-    let q0_crd_filename = base_path.join("tensor_Q_mode_0_crd");
-    let q1_seg_filename = base_path.join("tensor_Q_mode_1_seg");
-    let q1_crd_filename = base_path.join("tensor_Q_mode_1_crd");
-    let q2_seg_filename = base_path.join("tensor_Q_mode_2_seg");
-    let q2_crd_filename = base_path.join("tensor_Q_mode_2_crd");
-    let q3_seg_filename = base_path.join("tensor_Q_mode_3_seg");
-    let q3_crd_filename = base_path.join("tensor_Q_mode_3_crd");
-    let q_vals_filename = base_path.join("tensor_Q_mode_vals");
-
-    let k0_seg_filename = base_path.join("tensor_K_mode_0_seg");
-    let k0_crd_filename = base_path.join("tensor_K_mode_0_crd");
-    let k1_seg_filename = base_path.join("tensor_K_mode_1_seg");
-    let k1_crd_filename = base_path.join("tensor_K_mode_1_crd");
-    let k2_seg_filename = base_path.join("tensor_K_mode_2_seg");
-    let k2_crd_filename = base_path.join("tensor_K_mode_2_crd");
-    let k3_seg_filename = base_path.join("tensor_K_mode_3_seg");
-    let k3_crd_filename = base_path.join("tensor_K_mode_3_crd");
-    let k_vals_filename = base_path.join("tensor_K_mode_vals");
-
-    let v0_seg_filename = base_path.join("tensor_V_mode_0_seg");
-    let v0_crd_filename = base_path.join("tensor_V_mode_0_crd");
-    let v1_seg_filename = base_path.join("tensor_V_mode_1_seg");
-    let v1_crd_filename = base_path.join("tensor_V_mode_1_crd");
-    let v2_seg_filename = base_path.join("tensor_V_mode_2_seg");
-    let v2_crd_filename = base_path.join("tensor_V_mode_2_crd");
-    let v3_seg_filename = base_path.join("tensor_V_mode_3_seg");
-    let v3_crd_filename = base_path.join("tensor_V_mode_3_crd");
-    let v_vals_filename = base_path.join("tensor_V_mode_vals");
+    let data_dir = DataDir::open(Path::new(&formatted_dir).join(test_name)).unwrap();
+    let q0_seg_filename = data_dir.path("Q", TensorFile::Seg(0));
+    let q0_crd_filename = data_dir.path("Q", TensorFile::Crd(0));
+    let q1_seg_filename = data_dir.path("Q", TensorFile::Seg(1));
+    let q1_crd_filename = data_dir.path("Q", TensorFile::Crd(1));
+    let q2_seg_filename = data_dir.path("Q", TensorFile::Seg(2));
+    let q2_crd_filename = data_dir.path("Q", TensorFile::Crd(2));
+    let q3_seg_filename = data_dir.path("Q", TensorFile::Seg(3));
+    let q3_crd_filename = data_dir.path("Q", TensorFile::Crd(3));
+    let q_vals_filename = data_dir.path("Q", TensorFile::Vals);
+
+    let k0_seg_filename = data_dir.path("K", TensorFile::Seg(0));
+    let k0_crd_filename = data_dir.path("K", TensorFile::Crd(0));
+    let k1_seg_filename = data_dir.path("K", TensorFile::Seg(1));
+    let k1_crd_filename = data_dir.path("K", TensorFile::Crd(1));
+    let k2_seg_filename = data_dir.path("K", TensorFile::Seg(2));
+    let k2_crd_filename = data_dir.path("K", TensorFile::Crd(2));
+    let k3_seg_filename = data_dir.path("K", TensorFile::Seg(3));
+    let k3_crd_filename = data_dir.path("K", TensorFile::Crd(3));
+    let k_vals_filename = data_dir.path("K", TensorFile::Vals);
+
+    let v0_seg_filename = data_dir.path("V", TensorFile::Seg(0));
+    let v0_crd_filename = data_dir.path("V", TensorFile::Crd(0));
+    let v1_seg_filename = data_dir.path("V", TensorFile::Seg(1));
+    let v1_crd_filename = data_dir.path("V", TensorFile::Crd(1));
+    let v2_seg_filename = data_dir.path("V", TensorFile::Seg(2));
+    let v2_crd_filename = data_dir.path("V", TensorFile::Crd(2));
+    let v3_seg_filename = data_dir.path("V", TensorFile::Seg(3));
+    let v3_crd_filename = data_dir.path("V", TensorFile::Crd(3));
+    let v_vals_filename = data_dir.path("V", TensorFile::Vals);
 
     TestData {
         q0_seg: read_inputs(&q0_seg_filename),
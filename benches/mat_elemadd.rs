@@ -8,6 +8,7 @@ use criterion::{criterion_group, criterion_main, Criterion};
 
 use dam::utility_contexts::*;
 
+use comal::config::manifest::{DataDir, TensorFile};
 use comal::config::Data;
 
 use comal::templates::alu::make_alu;
@@ -75,18 +76,18 @@ fn load_data(test_name: &str) -> TestData {
     let contents = fs::read_to_string(filename).unwrap();
     let data: Data = toml::from_str(&contents).unwrap();
     let formatted_dir = data.sam_config.sam_path;
-    let base_path = Path::new(&formatted_dir).join(test_name);
-    let b0_seg_filename = base_path.join("tensor_B_mode_0_seg");
-    let b0_crd_filename = base_path.join("tensor_B_mode_0_crd");
-    let b1_seg_filename = base_path.join("tensor_B_mode_1_seg");
-    let b1_crd_filename = base_path.join("tensor_B_mode_1_crd");
-    let b_vals_filename = base_path.join("tensor_B_mode_vals");
-
-    let c0_seg_filename = base_path.join("tensor_C_mode_0_seg");
-    let c0_crd_filename = base_path.join("tensor_C_mode_0_crd");
-    let c1_seg_filename = base_path.join("tensor_C_mode_1_seg");
-    let c1_crd_filename = base_path.join("tensor_C_mode_1_crd");
-    let c_vals_filename = base_path.join("tensor_C_mode_vals");
+    let data_dir = DataDir::open(Path::new(&formatted_dir).join(test_name)).unwrap();
+    let b0_seg_filename = data_dir.path("B", TensorFile::Seg(0));
+    let b0_crd_filename = data_dir.path("B", TensorFile::Crd(0));
+    let b1_seg_filename = data_dir.path("B", TensorFile::Seg(1));
+    let b1_crd_filename = data_dir.path("B", TensorFile::Crd(1));
+    let b_vals_filename = data_dir.path("B", TensorFile::Vals);
+
+    let c0_seg_filename = data_dir.path("C", TensorFile::Seg(0));
+    let c0_crd_filename = data_dir.path("C", TensorFile::Crd(0));
+    let c1_seg_filename = data_dir.path("C", TensorFile::Seg(1));
+    let c1_crd_filename = data_dir.path("C", TensorFile::Crd(1));
+    let c_vals_filename = data_dir.path("C", TensorFile::Vals);
 
     TestData {
         b0_seg: read_inputs(&b0_seg_filename),
@@ -276,6 +277,5 @@ pub fn mat_elemadd_benchmark_large(c: &mut Criterion) {
     });
 }
 
-
 criterion_group!(elemadd_benches, mat_elemadd_benchmark_large,);
 criterion_main!(elemadd_benches);
@@ -1,14 +1,18 @@
 use std::io::Result;
 
 fn main() -> Result<()> {
-    prost_build::compile_protos(
-        &[
-            "tortilla/proto/comal.proto",
-            "tortilla/proto/tortilla.proto",
-            "tortilla/proto/stream.proto",
-            "tortilla/proto/ops.proto",
-        ],
-        &["tortilla/proto/"],
-    )?;
+    prost_build::Config::new()
+        // Lets graphs round-trip through JSON for hand editing
+        .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
+        .message_attribute(".", "#[serde(default)]")
+        .compile_protos(
+            &[
+                "tortilla/proto/comal.proto",
+                "tortilla/proto/tortilla.proto",
+                "tortilla/proto/stream.proto",
+                "tortilla/proto/ops.proto",
+            ],
+            &["tortilla/proto/"],
+        )?;
     Ok(())
 }
@@ -1,10 +1,10 @@
 #![allow(dead_code)]
 
-use std::{fs, str::FromStr, time::Instant};
+use std::{path::Path, str::FromStr, time::Instant};
 
 use comal::{
-    cli_common::{DamOptions, SamOptionFiles},
-    proto_driver::{build_from_proto, proto_headers::tortilla::ComalGraph, Channels},
+    cli_common::{CrdType, DamOptions, SamOptionFiles, SamOptions, ValueType},
+    proto_driver::{build_from_proto, graph_file::read_graph, Channels},
     templates::primitive::{Repsiggen, Token},
 };
 
@@ -13,7 +13,6 @@ use dam::{
     simulation::ProgramBuilder,
     utility_contexts::{ConsumerContext, GeneratorContext},
 };
-use prost::Message;
 
 use crate::channel_file::{ChannelFile, ChannelType};
 
@@ -21,7 +20,7 @@ mod channel_file;
 
 #[derive(Parser, Debug)]
 struct Cli {
-    /// Protobuffer containing a tortilla graph
+    /// Tortilla graph, either a binary protobuffer or JSON (.json)
     #[arg(long)]
     proto: String,
 
@@ -47,10 +46,7 @@ struct Cli {
 fn main() {
     let start = Instant::now();
     let args = Cli::parse();
-    let comal_graph = {
-        let file_contents = fs::read(&args.proto).unwrap();
-        ComalGraph::decode(file_contents.as_slice()).unwrap()
-    };
+    let comal_graph = read_graph(Path::new(&args.proto)).unwrap();
 
     let mut repsig = Channels::default();
     let mut vals = Channels::default();
@@ -125,16 +121,27 @@ fn main() {
         }
     });
 
-    build_from_proto(
+    let sam_options: SamOptions = (&args.sam_opts).into();
+    // Channel files are parsed as f32 values and u32 coordinates
+    if sam_options.value_type != ValueType::F32 || sam_options.crd_type != CrdType::U32 {
+        eprintln!("carl only supports f32 values and u32 coordinates");
+        std::process::exit(1);
+    }
+
+    if let Err(err) = build_from_proto::<f32, u32>(
         comal_graph,
         args.data.into(),
-        (&args.sam_opts).into(),
+        sam_options,
         &mut builder,
         &mut refs,
         &mut coords,
         &mut vals,
         &mut repsig,
-    );
+        &mut Default::default(),
+    ) {
+        eprintln!("Invalid graph: {}", err);
+        std::process::exit(1);
+    }
 
     refs.iter_remainders()
         .for_each(|remainder| builder.add_child(ConsumerContext::new(remainder)));
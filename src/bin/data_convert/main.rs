@@ -0,0 +1,37 @@
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use comal::templates::data_file::{binary_path, read_text, write_binary, DType, DataElement};
+
+/// Converts text seg/crd/vals files into the binary data format.
+/// Each output is written next to its input with `.bin` appended, where `read_inputs` picks it up.
+#[derive(Parser, Debug)]
+struct Cli {
+    /// Element type to store
+    #[arg(long, value_enum)]
+    dtype: DType,
+
+    /// Text files to convert
+    inputs: Vec<PathBuf>,
+}
+
+fn convert<T: DataElement>(input: &Path) -> anyhow::Result<()> {
+    let vals: Vec<T> = read_text(input)?;
+    write_binary(&binary_path(input), &vals)?;
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Cli::parse();
+    for input in &args.inputs {
+        match args.dtype {
+            DType::U32 => convert::<u32>(input),
+            DType::U64 => convert::<u64>(input),
+            DType::I32 => convert::<i32>(input),
+            DType::I64 => convert::<i64>(input),
+            DType::F32 => convert::<f32>(input),
+            DType::F64 => convert::<f64>(input),
+        }?;
+    }
+    Ok(())
+}
@@ -0,0 +1,21 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use comal::proto_driver::graph_file::{read_graph, write_graph};
+
+/// Converts a ComalGraph between the binary protobuf and JSON encodings.
+/// Each file's format comes from its extension: `.json` is JSON, anything else is binary.
+#[derive(Parser, Debug)]
+struct Cli {
+    /// Graph to read
+    input: PathBuf,
+
+    /// Graph to write
+    output: PathBuf,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Cli::parse();
+    let comal_graph = read_graph(&args.input)?;
+    write_graph(&comal_graph, &args.output)
+}
@@ -5,6 +5,7 @@ use comal::templates::primitive::Token;
 use comal::templates::scatter_gather::{Gather, Scatter};
 use comal::templates::stkn_dropper::StknDrop;
 
+use comal::config::manifest::{DataDir, TensorFile};
 use comal::templates::accumulator::{Reduce, ReduceData, Spacc1, Spacc1Data};
 use comal::templates::alu::make_alu;
 use comal::templates::array::{Array, ArrayData};
@@ -28,35 +29,36 @@ pub fn run_mha<'a>(
     short_chan_size: usize,
     long_chan_size: usize,
 ) -> ProgramBuilder<'a> {
-    let q0_seg_filename = base_path.join("tensor_Q_mode_0_seg");
-    let q0_crd_filename = base_path.join("tensor_Q_mode_0_crd");
-    let q1_seg_filename = base_path.join("tensor_Q_mode_1_seg");
-    let q1_crd_filename = base_path.join("tensor_Q_mode_1_crd");
-    let q2_seg_filename = base_path.join("tensor_Q_mode_2_seg");
-    let q2_crd_filename = base_path.join("tensor_Q_mode_2_crd");
-    let q3_seg_filename = base_path.join("tensor_Q_mode_3_seg");
-    let q3_crd_filename = base_path.join("tensor_Q_mode_3_crd");
-    let q_vals_filename = base_path.join("tensor_Q_mode_vals");
-
-    let k0_seg_filename = base_path.join("tensor_K_mode_0_seg");
-    let k0_crd_filename = base_path.join("tensor_K_mode_0_crd");
-    let k1_seg_filename = base_path.join("tensor_K_mode_1_seg");
-    let k1_crd_filename = base_path.join("tensor_K_mode_1_crd");
-    let k2_seg_filename = base_path.join("tensor_K_mode_2_seg");
-    let k2_crd_filename = base_path.join("tensor_K_mode_2_crd");
-    let k3_seg_filename = base_path.join("tensor_K_mode_3_seg");
-    let k3_crd_filename = base_path.join("tensor_K_mode_3_crd");
-    let k_vals_filename = base_path.join("tensor_K_mode_vals");
-
-    let v0_seg_filename = base_path.join("tensor_V_mode_0_seg");
-    let v0_crd_filename = base_path.join("tensor_V_mode_0_crd");
-    let v1_seg_filename = base_path.join("tensor_V_mode_1_seg");
-    let v1_crd_filename = base_path.join("tensor_V_mode_1_crd");
-    let v2_seg_filename = base_path.join("tensor_V_mode_2_seg");
-    let v2_crd_filename = base_path.join("tensor_V_mode_2_crd");
-    let v3_seg_filename = base_path.join("tensor_V_mode_3_seg");
-    let v3_crd_filename = base_path.join("tensor_V_mode_3_crd");
-    let v_vals_filename = base_path.join("tensor_V_mode_vals");
+    let data_dir = DataDir::open(base_path).unwrap();
+    let q0_seg_filename = data_dir.path("Q", TensorFile::Seg(0));
+    let q0_crd_filename = data_dir.path("Q", TensorFile::Crd(0));
+    let q1_seg_filename = data_dir.path("Q", TensorFile::Seg(1));
+    let q1_crd_filename = data_dir.path("Q", TensorFile::Crd(1));
+    let q2_seg_filename = data_dir.path("Q", TensorFile::Seg(2));
+    let q2_crd_filename = data_dir.path("Q", TensorFile::Crd(2));
+    let q3_seg_filename = data_dir.path("Q", TensorFile::Seg(3));
+    let q3_crd_filename = data_dir.path("Q", TensorFile::Crd(3));
+    let q_vals_filename = data_dir.path("Q", TensorFile::Vals);
+
+    let k0_seg_filename = data_dir.path("K", TensorFile::Seg(0));
+    let k0_crd_filename = data_dir.path("K", TensorFile::Crd(0));
+    let k1_seg_filename = data_dir.path("K", TensorFile::Seg(1));
+    let k1_crd_filename = data_dir.path("K", TensorFile::Crd(1));
+    let k2_seg_filename = data_dir.path("K", TensorFile::Seg(2));
+    let k2_crd_filename = data_dir.path("K", TensorFile::Crd(2));
+    let k3_seg_filename = data_dir.path("K", TensorFile::Seg(3));
+    let k3_crd_filename = data_dir.path("K", TensorFile::Crd(3));
+    let k_vals_filename = data_dir.path("K", TensorFile::Vals);
+
+    let v0_seg_filename = data_dir.path("V", TensorFile::Seg(0));
+    let v0_crd_filename = data_dir.path("V", TensorFile::Crd(0));
+    let v1_seg_filename = data_dir.path("V", TensorFile::Seg(1));
+    let v1_crd_filename = data_dir.path("V", TensorFile::Crd(1));
+    let v2_seg_filename = data_dir.path("V", TensorFile::Seg(2));
+    let v2_crd_filename = data_dir.path("V", TensorFile::Crd(2));
+    let v3_seg_filename = data_dir.path("V", TensorFile::Seg(3));
+    let v3_crd_filename = data_dir.path("V", TensorFile::Crd(3));
+    let v_vals_filename = data_dir.path("V", TensorFile::Vals);
 
     let q0_seg = read_inputs::<u32>(&q0_seg_filename);
     let q0_crd = read_inputs::<u32>(&q0_crd_filename);
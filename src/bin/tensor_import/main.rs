@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use comal::config::manifest::DataDir;
+use comal::utils::import::{read_frostt, read_matrix_market, ImportedTensor};
+
+/// Imports a Matrix Market (.mtx) or FROSTT (.tns) tensor into a data directory,
+/// writing the compressed seg/crd/vals files the proto driver reads.
+#[derive(Parser, Debug)]
+struct Cli {
+    /// Tensor file, read according to its extension
+    input: PathBuf,
+
+    /// Data directory to write into. Files are named by its manifest if it has one.
+    #[arg(long)]
+    data: PathBuf,
+
+    /// Name of the tensor in the graph, e.g. B
+    #[arg(long)]
+    tensor: String,
+
+    /// Modes from the outermost level in, e.g. 1,0 to store a matrix by column
+    #[arg(long, value_delimiter = ',')]
+    order: Option<Vec<usize>>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Cli::parse();
+    let order = args.order.as_deref();
+    let imported: ImportedTensor<u32, f64> =
+        match args.input.extension().and_then(|ext| ext.to_str()) {
+            Some("mtx") => read_matrix_market(&args.input, order)?,
+            Some("tns") => read_frostt(&args.input, order)?,
+            _ => anyhow::bail!("{:?} is neither a .mtx nor a .tns file", args.input),
+        };
+    let data = DataDir::open(&args.data)?;
+    imported.write(&data, &args.tensor)?;
+    println!(
+        "Wrote {} with shape {:?} and {} nonzeros",
+        args.tensor,
+        imported.shape,
+        imported.tree.to_coo().len()
+    );
+    Ok(())
+}
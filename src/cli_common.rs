@@ -1,4 +1,4 @@
-use clap::Args;
+use clap::{Args, ValueEnum};
 use dam::{
     shim::RunMode,
     simulation::{
@@ -6,7 +6,10 @@ use dam::{
     },
 };
 
-use crate::config::rd_scanner::CompressedCrdRdScanConfig;
+use crate::config::{
+    channels::ChannelConfig,
+    rd_scanner::{CompressedCrdRdScanConfig, TileRdScanConfig},
+};
 
 #[derive(Args, Debug, Clone, Copy)]
 pub struct DamOptions {
@@ -40,16 +43,86 @@ impl From<DamOptions> for RunOptions {
     }
 }
 
+/// Element type of the value streams in a proto graph
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValueType {
+    #[default]
+    F32,
+    F64,
+    I32,
+    I64,
+}
+
+/// Element type of the coordinate and reference streams in a proto graph
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CrdType {
+    #[default]
+    U32,
+    U64,
+}
+
 #[derive(Args, Debug, Clone, Default)]
 pub struct SamOptionFiles {
     /// TOML file containing a [[CompressedRdScanConfig]]
     #[arg(long)]
     compressed_read_config: Option<String>,
+
+    /// TOML file containing a [[TileRdScanConfig]]
+    #[arg(long)]
+    tile_read_config: Option<String>,
+
+    /// TOML file containing per-kind and per-stream [[ChannelConfig]] overrides
+    #[arg(long)]
+    channel_config: Option<String>,
+
+    #[command(flatten)]
+    run_opts: SamRunOptions,
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+/// How the graph is run: its batches, parallel lanes and element types
+#[derive(Args, Debug, Clone, Default)]
+pub struct SamRunOptions {
+    /// Root refs to run as separate batches, e.g. `--root-batches 0,1,2`
+    #[arg(long, value_delimiter = ',')]
+    root_batches: Vec<u64>,
+
+    /// Top-level stream ID to split the graph into parallel lanes at
+    #[arg(long, requires = "par_factor")]
+    par_stream: Option<u64>,
+
+    /// Number of parallel lanes for `--par-stream`
+    #[arg(long, requires = "par_stream")]
+    par_factor: Option<usize>,
+
+    /// Type of the values the graph computes on
+    #[arg(long, value_enum, default_value_t)]
+    value_type: ValueType,
+
+    /// Type of the coordinates and references, u64 for tensors with more than 2^32 entries
+    #[arg(long, value_enum, default_value_t)]
+    crd_type: CrdType,
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct SamOptions {
     pub compressed_read_config: CompressedCrdRdScanConfig,
+    pub tile_read_config: TileRdScanConfig,
+    pub channel_config: ChannelConfig,
+
+    /// Root refs emitted one batch after another; empty means the single batch 0
+    pub root_batches: Vec<u64>,
+
+    /// Stream to split on and the number of lanes, see [[crate::proto_driver::parallelize]]
+    pub parallelize: Option<(u64, usize)>,
+
+    pub value_type: ValueType,
+    pub crd_type: CrdType,
+}
+
+impl SamOptions {
+    pub fn num_batches(&self) -> usize {
+        self.root_batches.len().max(1)
+    }
 }
 
 // Defining a read_or_default conversion from SamOptionFiles to SamOptions
@@ -57,6 +130,12 @@ impl From<&SamOptionFiles> for SamOptions {
     fn from(val: &SamOptionFiles) -> Self {
         SamOptions {
             compressed_read_config: val.try_into().unwrap(),
+            tile_read_config: val.try_into().unwrap(),
+            channel_config: val.try_into().unwrap(),
+            root_batches: val.run_opts.root_batches.clone(),
+            parallelize: val.run_opts.par_stream.zip(val.run_opts.par_factor),
+            value_type: val.run_opts.value_type,
+            crd_type: val.run_opts.crd_type,
         }
     }
 }
@@ -81,3 +160,5 @@ macro_rules! config_type {
 }
 
 config_type!(compressed_read_config, CompressedCrdRdScanConfig);
+config_type!(tile_read_config, TileRdScanConfig);
+config_type!(channel_config, ChannelConfig);
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::proto_driver::validate::StreamKind;
+
+/// Buffering of a single channel. Unset fields fall through to the next, less specific, level.
+#[derive(Debug, Deserialize, Copy, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelSettings {
+    /// Number of elements the channel can hold
+    pub depth: Option<usize>,
+
+    /// Cycles before an enqueued element is visible to the receiver
+    pub latency: Option<u64>,
+
+    /// Cycles before a dequeue frees up space for the sender
+    pub resp_latency: Option<u64>,
+}
+
+impl ChannelSettings {
+    fn or(self, other: Self) -> Self {
+        Self {
+            depth: self.depth.or(other.depth),
+            latency: self.latency.or(other.latency),
+            resp_latency: self.resp_latency.or(other.resp_latency),
+        }
+    }
+}
+
+/// Per-stream channel overrides for proto graphs, e.g.
+/// ```toml
+/// [default]
+/// depth = 1024
+///
+/// [crd]
+/// depth = 8
+///
+/// [streams]
+/// 12 = { depth = 2, latency = 4 }
+/// ```
+/// A stream ID entry wins over its kind (`crd`, `ref`, `val`, `repsig`), which wins over `default`.
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ChannelConfig {
+    #[serde(default)]
+    pub default: ChannelSettings,
+
+    #[serde(default)]
+    pub crd: ChannelSettings,
+
+    #[serde(default)]
+    pub r#ref: ChannelSettings,
+
+    #[serde(default)]
+    pub val: ChannelSettings,
+
+    #[serde(default)]
+    pub repsig: ChannelSettings,
+
+    /// Keyed by the stream ID in the top-level graph
+    #[serde(default)]
+    pub streams: HashMap<String, ChannelSettings>,
+}
+
+impl ChannelConfig {
+    fn kind(&self, kind: Option<StreamKind>) -> ChannelSettings {
+        match kind {
+            Some(StreamKind::Crd) => self.crd,
+            Some(StreamKind::Ref) => self.r#ref,
+            Some(StreamKind::Val) => self.val,
+            Some(StreamKind::Repsig) => self.repsig,
+            None => ChannelSettings::default(),
+        }
+    }
+
+    /// The most specific settings for a stream; `kind` is None for channels outside the four stream kinds,
+    /// and `id` is None for streams without a global ID.
+    pub fn resolve(&self, kind: Option<StreamKind>, id: Option<u64>) -> ChannelSettings {
+        let stream = id
+            .and_then(|id| self.streams.get(&id.to_string()).copied())
+            .unwrap_or_default();
+        stream.or(self.kind(kind)).or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChannelConfig, ChannelSettings};
+    use crate::proto_driver::validate::StreamKind;
+
+    #[test]
+    fn resolve_test() {
+        let config: ChannelConfig = toml::from_str(
+            r#"
+            [default]
+            depth = 64
+            latency = 2
+
+            [crd]
+            depth = 8
+
+            [streams]
+            12 = { depth = 2, resp_latency = 3 }
+            "#,
+        )
+        .unwrap();
+        let settings = |depth, latency, resp_latency| ChannelSettings {
+            depth: Some(depth),
+            latency: Some(latency),
+            resp_latency,
+        };
+        let (val, crd) = (Some(StreamKind::Val), Some(StreamKind::Crd));
+        assert_eq!(config.resolve(val, Some(3)), settings(64, 2, None));
+        assert_eq!(config.resolve(crd, Some(3)), settings(8, 2, None));
+        assert_eq!(config.resolve(crd, Some(12)), settings(2, 2, Some(3)));
+        assert_eq!(config.resolve(crd, None), settings(8, 2, None));
+        assert_eq!(config.resolve(None, Some(3)), settings(64, 2, None));
+    }
+}
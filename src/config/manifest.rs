@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::templates::data_file::DType;
+
+/// Name of the manifest inside a data directory
+pub const MANIFEST_FILE: &str = "manifest.toml";
+
+/// File name patterns, relative to the data directory. `{tensor}`, `{mode}` and `{tile}`
+/// are replaced by the tensor name, mode and tile index.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct FilePatterns {
+    pub seg: Option<String>,
+    pub crd: Option<String>,
+    pub shape_file: Option<String>,
+    pub vals: Option<String>,
+    pub tile_seg: Option<String>,
+    pub tile_crd: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+pub struct TensorManifest {
+    /// Size of each mode, in place of the shape file
+    pub shape: Option<Vec<u64>>,
+
+    /// Type of the stored values
+    pub dtype: Option<DType>,
+
+    /// Level format of each mode, e.g. `["compressed", "compressed"]`
+    #[serde(default)]
+    pub formats: Vec<String>,
+
+    #[serde(flatten)]
+    pub files: FilePatterns,
+}
+
+/// Describes the tensors of a data directory laid out by other tools, e.g.
+/// ```toml
+/// [default]
+/// vals = "{tensor}_vals.txt"
+///
+/// [tensors.B]
+/// shape = [10, 12]
+/// dtype = "f32"
+/// formats = ["dense", "compressed"]
+/// seg = "B{mode}_pos.txt"
+/// crd = "B{mode}_idx.txt"
+/// ```
+/// A tensor's own patterns win over `default`, which wins over the `tensor_{tensor}_mode_...` layout.
+#[derive(Debug, Deserialize, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DataManifest {
+    #[serde(default)]
+    pub default: FilePatterns,
+
+    #[serde(default)]
+    pub tensors: HashMap<String, TensorManifest>,
+}
+
+/// A file of a tensor's on-disk representation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TensorFile {
+    Seg(u64),
+    Crd(u64),
+    Shape,
+    Vals,
+    TileSeg { tile: usize, mode: u64 },
+    TileCrd { tile: usize, mode: u64 },
+}
+
+impl TensorFile {
+    fn pattern<'a>(&self, patterns: &'a FilePatterns) -> Option<&'a String> {
+        match self {
+            TensorFile::Seg(_) => patterns.seg.as_ref(),
+            TensorFile::Crd(_) => patterns.crd.as_ref(),
+            TensorFile::Shape => patterns.shape_file.as_ref(),
+            TensorFile::Vals => patterns.vals.as_ref(),
+            TensorFile::TileSeg { .. } => patterns.tile_seg.as_ref(),
+            TensorFile::TileCrd { .. } => patterns.tile_crd.as_ref(),
+        }
+    }
+
+    fn default_pattern(&self) -> &'static str {
+        match self {
+            TensorFile::Seg(_) => "tensor_{tensor}_mode_{mode}_seg",
+            TensorFile::Crd(_) => "tensor_{tensor}_mode_{mode}_crd",
+            TensorFile::Shape => "tensor_{tensor}_mode_shape",
+            TensorFile::Vals => "tensor_{tensor}_mode_vals",
+            TensorFile::TileSeg { .. } => "tensor_{tensor}_tile_{tile}_mode_{mode}_seg",
+            TensorFile::TileCrd { .. } => "tensor_{tensor}_tile_{tile}_mode_{mode}_crd",
+        }
+    }
+
+    fn mode(&self) -> Option<u64> {
+        match self {
+            TensorFile::Seg(mode) | TensorFile::Crd(mode) => Some(*mode),
+            TensorFile::TileSeg { mode, .. } | TensorFile::TileCrd { mode, .. } => Some(*mode),
+            TensorFile::Shape | TensorFile::Vals => None,
+        }
+    }
+
+    fn tile(&self) -> Option<usize> {
+        match self {
+            TensorFile::TileSeg { tile, .. } | TensorFile::TileCrd { tile, .. } => Some(*tile),
+            _ => None,
+        }
+    }
+}
+
+/// A directory of tensor data files, named by its manifest if it has one.
+#[derive(Debug, Clone, Default)]
+pub struct DataDir {
+    pub root: PathBuf,
+    pub manifest: DataManifest,
+}
+
+impl DataDir {
+    /// Reads the directory's manifest; without one, files use the `tensor_{tensor}_mode_...` layout.
+    pub fn open(root: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let root = root.into();
+        let manifest_path = root.join(MANIFEST_FILE);
+        let manifest = if manifest_path.exists() {
+            toml::from_str(&std::fs::read_to_string(manifest_path)?)?
+        } else {
+            DataManifest::default()
+        };
+        Ok(Self { root, manifest })
+    }
+
+    pub fn tensor(&self, tensor: &str) -> Option<&TensorManifest> {
+        self.manifest.tensors.get(tensor)
+    }
+
+    pub fn path(&self, tensor: &str, file: TensorFile) -> PathBuf {
+        let pattern = (self.tensor(tensor))
+            .and_then(|manifest| file.pattern(&manifest.files))
+            .or_else(|| file.pattern(&self.manifest.default))
+            .map_or(file.default_pattern(), String::as_str);
+        let mut name = pattern.replace("{tensor}", tensor);
+        if let Some(mode) = file.mode() {
+            name = name.replace("{mode}", &mode.to_string());
+        }
+        if let Some(tile) = file.tile() {
+            name = name.replace("{tile}", &tile.to_string());
+        }
+        self.root.join(name)
+    }
+
+    /// Size of a mode from the manifest, if it gives the tensor's shape.
+    pub fn mode_size(&self, tensor: &str, mode: u64) -> Option<u64> {
+        let shape = self.tensor(tensor)?.shape.as_ref()?;
+        shape.get(mode as usize).copied()
+    }
+
+    /// Level format of a mode from the manifest, if it lists the tensor's formats.
+    pub fn format(&self, tensor: &str, mode: u64) -> Option<&str> {
+        let formats = &self.tensor(tensor)?.formats;
+        formats.get(mode as usize).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DataDir, DataManifest, TensorFile};
+    use crate::templates::data_file::DType;
+
+    #[test]
+    fn resolve_paths_test() {
+        let manifest: DataManifest = toml::from_str(
+            r#"
+            [default]
+            vals = "{tensor}_vals.txt"
+
+            [tensors.B]
+            shape = [10, 12]
+            dtype = "f32"
+            formats = ["dense", "compressed"]
+            seg = "B{mode}_pos.txt"
+            tile_crd = "tiles/B_{tile}_{mode}_idx"
+            "#,
+        )
+        .unwrap();
+        let data = DataDir {
+            root: "data".into(),
+            manifest,
+        };
+        let path = |tensor, file| data.path(tensor, file).to_str().unwrap().to_string();
+
+        assert_eq!(path("B", TensorFile::Seg(1)), "data/B1_pos.txt");
+        assert_eq!(path("B", TensorFile::Crd(1)), "data/tensor_B_mode_1_crd");
+        assert_eq!(path("B", TensorFile::Vals), "data/B_vals.txt");
+        assert_eq!(path("C", TensorFile::Vals), "data/C_vals.txt");
+        assert_eq!(path("C", TensorFile::Shape), "data/tensor_C_mode_shape");
+        assert_eq!(
+            path("B", TensorFile::TileCrd { tile: 3, mode: 0 }),
+            "data/tiles/B_3_0_idx"
+        );
+
+        assert_eq!(data.mode_size("B", 1), Some(12));
+        assert_eq!(data.mode_size("C", 1), None);
+        assert_eq!(data.format("B", 0), Some("dense"));
+        assert_eq!(data.tensor("B").unwrap().dtype, Some(DType::F32));
+    }
+}
@@ -1,3 +1,5 @@
+pub mod channels;
+pub mod manifest;
 pub mod rd_scanner;
 
 use serde::Deserialize;
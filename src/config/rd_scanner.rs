@@ -39,3 +39,25 @@ impl Default for CompressedCrdRdScanConfig {
         }
     }
 }
+
+#[derive(Debug, Deserialize, Copy, Clone)]
+pub struct TileRdScanConfig {
+    /// Latency before emitting new values in a scanner
+    pub output_latency: u64,
+
+    /// Initiation interval of the output pipeline -- the delay between consecutive outputs
+    pub sequential_interval: u64,
+
+    /// Pipeline bubble when switching to the next tile's segment/coord arrays
+    pub tile_switch_latency: u64,
+}
+
+impl Default for TileRdScanConfig {
+    fn default() -> Self {
+        Self {
+            output_latency: 1,
+            sequential_interval: 1,
+            tile_switch_latency: 0,
+        }
+    }
+}
@@ -1,11 +1,11 @@
 #![allow(dead_code)]
 
-use std::{fs, time::Instant};
+use std::{fs, path::Path, time::Instant};
 
-use cli_common::{DamOptions, SamOptionFiles};
+use cli_common::{DamOptions, SamOptionFiles, SamOptions};
+use config::manifest::DataDir;
 use dam::{logging::LogEvent, simulation::*};
-use prost::Message;
-use proto_driver::{parse_proto, proto_headers::tortilla::ComalGraph};
+use proto_driver::{dot::to_dot, graph_file::read_graph, parse_proto};
 
 mod cli_common;
 mod config;
@@ -17,7 +17,7 @@ use clap::Parser;
 
 #[derive(Parser, Debug)]
 struct Cli {
-    /// Protobuffer containing a tortilla graph
+    /// Tortilla graph, either a binary protobuffer or JSON (.json)
     #[arg(long)]
     proto: String,
 
@@ -29,6 +29,22 @@ struct Cli {
     #[arg(long)]
     breakdowns: bool,
 
+    /// Write the graph as DOT, annotated with proto op types, tensors and stream IDs
+    #[arg(long)]
+    dot: Option<String>,
+
+    /// Directory to write the output tensors to, in the same format as the input data
+    #[arg(long)]
+    output_dir: Option<String>,
+
+    /// Compare the output tensors against the reference files in the data directory
+    #[arg(long)]
+    check: bool,
+
+    /// Absolute tolerance used when comparing output values in --check mode
+    #[arg(long, default_value_t = 1e-5)]
+    tolerance: f64,
+
     #[command(flatten)]
     dam_opts: DamOptions,
 
@@ -39,17 +55,31 @@ struct Cli {
 fn main() {
     let start = Instant::now();
     let args = Cli::parse();
-    let comal_graph = {
-        let file_contents = fs::read(&args.proto).unwrap();
-        ComalGraph::decode(file_contents.as_slice()).unwrap()
-    };
-    let program_builder = parse_proto(comal_graph, args.data.into(), (&args.sam_opts).into());
+    let comal_graph = read_graph(Path::new(&args.proto)).unwrap();
+    if let Some(dot_file) = &args.dot {
+        // Still run the graph, parse_proto decides whether it is valid
+        let written = to_dot(&comal_graph)
+            .map_err(|err| err.to_string())
+            .and_then(|dot| fs::write(dot_file, dot).map_err(|err| err.to_string()));
+        if let Err(err) = written {
+            eprintln!("Can't write {}: {}", dot_file, err);
+        }
+    }
+    let sam_options: SamOptions = (&args.sam_opts).into();
+    let num_batches = sam_options.num_batches();
+    let (program_builder, outputs) =
+        match parse_proto(comal_graph, (&args.data).into(), sam_options) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                eprintln!("Invalid graph: {}", err);
+                std::process::exit(1);
+            }
+        };
     let end_parse = Instant::now();
     if args.breakdowns {
         println!("Parse Time: {:?}", end_parse - start);
     }
     let initialized = program_builder.initialize(args.dam_opts.into()).unwrap();
-    println!("{}", initialized.to_dot_string());
 
     let initialized_time = Instant::now();
     if args.breakdowns {
@@ -61,4 +91,35 @@ fn main() {
         println!("Execution Time: {:?}", initialized_time.elapsed());
     }
     println!("Elapsed Cycles: {}", executed.elapsed_cycles().unwrap());
+    if num_batches > 1 {
+        for (batch, cycles) in outputs.batch_cycles(num_batches).iter().enumerate() {
+            println!("Batch {} Cycles: {}", batch, cycles);
+        }
+    }
+
+    if let Some(output_dir) = args.output_dir {
+        if let Err(err) = outputs.write_to_dir(Path::new(&output_dir)) {
+            eprintln!("Can't write the outputs to {}: {}", output_dir, err);
+            std::process::exit(1);
+        }
+    }
+
+    if args.check {
+        let data = match DataDir::open(&args.data) {
+            Ok(data) => data,
+            Err(err) => {
+                eprintln!("Can't open the reference data in {}: {}", args.data, err);
+                std::process::exit(1);
+            }
+        };
+        let mismatches = outputs.check_against_dir(&data, args.tolerance);
+        if mismatches.is_empty() {
+            println!("All outputs match the reference");
+        } else {
+            mismatches
+                .iter()
+                .for_each(|mismatch| eprintln!("Mismatch: {}", mismatch));
+            std::process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,1549 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::custom::{find_custom_op, CustomOpArgs};
+use super::outputs::TensorOutputs;
+use super::parallelize::{parallelize, root_levels, LaneSplit, ParallelPlan};
+use super::proto_headers::tortilla::operation::*;
+use super::proto_headers::tortilla::*;
+use super::util::{get_crd_id, get_ref_id, get_repsig_id, get_val_id, AsStreamID};
+use super::validate::{validate, GraphError, OpRef, StreamKind};
+use super::{find_block_streams, Channels, CrdElement, ValueElement, ST};
+
+use crate::cli_common::SamOptions;
+use crate::config::manifest::{DataDir, TensorFile};
+use crate::templates::accumulator::{Reduce, ReduceData, Spacc1, Spacc1Data, SpaccN, SpaccNData};
+use crate::templates::alu::{
+    check_chain, left_fold_operands, make_chained_alu, ChainedOp, ChainedStage,
+};
+use crate::templates::array::{Array, ArrayData};
+use crate::templates::crd_alu::{
+    CrdBinaryAlu, CrdBinaryAluData, CrdBinaryOp, CrdScalarAlu, CrdScalarOp,
+};
+use crate::templates::crd_manager::{CrdDrop, CrdHold, CrdManagerData};
+use crate::templates::crd_masker::{CrdMask, CrdMaskData, MaskPredicate};
+use crate::templates::data_file::{data_exists, read_data, DataElement};
+use crate::templates::joiner::{
+    CrdJoinerData, CrdJoinerNData, Intersect, IntersectN, Union, UnionN,
+};
+use crate::templates::primitive::{Repsiggen, Token};
+use crate::templates::rd_scanner::{
+    CompressedCrdRdScan, RdScanData, TileRdScan, UncompressedCrdRdScan,
+};
+use crate::templates::repeat::{RepSigGenData, Repeat, RepeatData, RepeatSigGen};
+use crate::templates::scatter_gather::{OrderedGather, Scatter};
+use crate::templates::tensor::{Adapter, PrimitiveType, Tensor};
+use crate::templates::unary::UnaryMax;
+use crate::templates::wr_scanner::{CompressedWrScan, ValsWrScan};
+use dam::context_tools::{Context, DAMType, Receiver, Sender};
+use dam::simulation::ProgramBuilder;
+use dam::utility_contexts::{BroadcastContext, GeneratorContext};
+use num::Zero;
+
+type Block<VT> = Tensor<'static, VT>;
+
+#[allow(clippy::too_many_arguments)]
+pub fn build_from_proto<'a, VT: ValueElement, CT: CrdElement>(
+    comal_graph: ComalGraph,
+    base_path: PathBuf,
+    sam_options: SamOptions,
+    builder: &mut ProgramBuilder<'a>,
+    refmap: &mut Channels<'a, Token<CT, ST>>,
+    crdmap: &mut Channels<'a, Token<CT, ST>>,
+    valmap: &mut Channels<'a, Token<VT, ST>>,
+    repmap: &mut Channels<'a, Repsiggen>,
+    outputs: &mut TensorOutputs,
+) -> Result<(), GraphError> {
+    let channel_config = Arc::new(sam_options.channel_config.clone());
+    refmap.configure(StreamKind::Ref, channel_config.clone());
+    crdmap.configure(StreamKind::Crd, channel_config.clone());
+    valmap.configure(StreamKind::Val, channel_config.clone());
+    repmap.configure(StreamKind::Repsig, channel_config.clone());
+    let mut blockmap = Channels::default();
+    blockmap.configure(StreamKind::Val, channel_config);
+
+    // Streams already in the maps were set up by the caller, so they count as produced
+    validate(
+        &comal_graph,
+        |kind, id| match kind {
+            StreamKind::Ref => refmap.contains(id),
+            StreamKind::Crd => crdmap.contains(id),
+            StreamKind::Val => valmap.contains(id),
+            StreamKind::Repsig => repmap.contains(id),
+        },
+        false,
+    )?;
+    let comal_graph = match sam_options.parallelize {
+        Some((stream, factor)) => {
+            let (comal_graph, plan) = parallelize(&comal_graph, stream, factor)?;
+            build_lanes(&plan, builder, refmap, crdmap, valmap);
+            comal_graph
+        }
+        None => comal_graph,
+    };
+    let data = DataDir::open(&base_path).map_err(|err| GraphError::BadDataDir {
+        path: base_path,
+        reason: err.to_string(),
+    })?;
+    let funcs: HashMap<String, ProgramGraph> = comal_graph
+        .funcs
+        .into_iter()
+        .map(|func| (func.name.clone(), func))
+        .collect();
+    build_graph(
+        comal_graph.graph.ok_or(GraphError::MissingGraph)?,
+        None,
+        &funcs,
+        &data,
+        &sam_options,
+        builder,
+        refmap,
+        crdmap,
+        valmap,
+        repmap,
+        &mut blockmap,
+        &HashSet::new(),
+        outputs,
+    )
+}
+
+fn add_scatter<'a, T: DAMType>(
+    split: &LaneSplit,
+    map: &mut Channels<'a, Token<T, ST>>,
+    builder: &mut ProgramBuilder<'a>,
+) where
+    Scatter<T, ST>: Context,
+{
+    let mut scatter = Scatter::new(map.get_receiver(split.id, builder));
+    (split.lanes.iter()).for_each(|lane| scatter.add_target(map.get_sender(*lane, builder)));
+    builder.add_child(scatter);
+}
+
+/// Scatters the split stream like any other input, handing back `copies` copies of it for the
+/// gathers to follow.
+fn add_split<'a, T: DAMType>(
+    split: &LaneSplit,
+    copies: usize,
+    map: &mut Channels<'a, Token<T, ST>>,
+    builder: &mut ProgramBuilder<'a>,
+) -> Vec<Receiver<Token<T, ST>>>
+where
+    Scatter<T, ST>: Context,
+{
+    let mut broadcast = BroadcastContext::new(map.get_receiver(split.id, builder));
+    let (to_scatter, from_broadcast) = map.new_channel(builder, None);
+    broadcast.add_target(to_scatter);
+    let controls = (0..copies)
+        .map(|_| {
+            let (snd, rcv) = map.new_channel(builder, None);
+            broadcast.add_target(snd);
+            rcv
+        })
+        .collect();
+    builder.add_child(broadcast);
+
+    let mut scatter = Scatter::new(from_broadcast);
+    (split.lanes.iter()).for_each(|lane| scatter.add_target(map.get_sender(*lane, builder)));
+    builder.add_child(scatter);
+    controls
+}
+
+fn add_gather<'a, T: DAMType, C: DAMType>(
+    split: &LaneSplit,
+    control: Receiver<Token<C, ST>>,
+    map: &mut Channels<'a, Token<T, ST>>,
+    builder: &mut ProgramBuilder<'a>,
+) where
+    OrderedGather<T, C, ST>: Context,
+{
+    let depth = split.depth.try_into().unwrap();
+    let mut gather = OrderedGather::new(control, map.get_sender(split.id, builder), depth);
+    (split.lanes.iter()).for_each(|lane| gather.add_target(map.get_receiver(*lane, builder)));
+    builder.add_child(gather);
+}
+
+fn add_gathers<'a, VT: ValueElement, CT: CrdElement, C: DAMType>(
+    plan: &ParallelPlan,
+    controls: Vec<Receiver<Token<C, ST>>>,
+    builder: &mut ProgramBuilder<'a>,
+    refmap: &mut Channels<'a, Token<CT, ST>>,
+    crdmap: &mut Channels<'a, Token<CT, ST>>,
+    valmap: &mut Channels<'a, Token<VT, ST>>,
+) {
+    for (split, control) in plan.gathers.iter().zip(controls) {
+        match split.kind {
+            StreamKind::Ref => add_gather(split, control, refmap, builder),
+            StreamKind::Crd => add_gather(split, control, crdmap, builder),
+            StreamKind::Val => add_gather(split, control, valmap, builder),
+            StreamKind::Repsig => unreachable!("parallelize never splits repsig streams"),
+        }
+    }
+}
+
+/// Sets up the Scatter/Gather contexts of a parallelized graph. The lane ends are left in the maps
+/// for the replicated ops to pick up.
+fn build_lanes<'a, VT: ValueElement, CT: CrdElement>(
+    plan: &ParallelPlan,
+    builder: &mut ProgramBuilder<'a>,
+    refmap: &mut Channels<'a, Token<CT, ST>>,
+    crdmap: &mut Channels<'a, Token<CT, ST>>,
+    valmap: &mut Channels<'a, Token<VT, ST>>,
+) {
+    let copies = plan.gathers.len();
+    for split in &plan.scatters {
+        if split.id == plan.split {
+            match split.kind {
+                StreamKind::Ref => {
+                    let controls = add_split(split, copies, refmap, builder);
+                    add_gathers(plan, controls, builder, refmap, crdmap, valmap);
+                }
+                StreamKind::Crd => {
+                    let controls = add_split(split, copies, crdmap, builder);
+                    add_gathers(plan, controls, builder, refmap, crdmap, valmap);
+                }
+                StreamKind::Val => {
+                    let controls = add_split(split, copies, valmap, builder);
+                    add_gathers(plan, controls, builder, refmap, crdmap, valmap);
+                }
+                StreamKind::Repsig => unreachable!("parallelize never splits repsig streams"),
+            }
+            continue;
+        }
+        match split.kind {
+            StreamKind::Ref => add_scatter(split, refmap, builder),
+            StreamKind::Crd => add_scatter(split, crdmap, builder),
+            StreamKind::Val => add_scatter(split, valmap, builder),
+            StreamKind::Repsig => unreachable!("parallelize never splits repsig streams"),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn build_graph<'a, VT: ValueElement, CT: CrdElement>(
+    graph: ProgramGraph,
+    func: Option<&str>,
+    funcs: &HashMap<String, ProgramGraph>,
+    data: &DataDir,
+    sam_options: &SamOptions,
+    builder: &mut ProgramBuilder<'a>,
+    refmap: &mut Channels<'a, Token<CT, ST>>,
+    crdmap: &mut Channels<'a, Token<CT, ST>>,
+    valmap: &mut Channels<'a, Token<VT, ST>>,
+    repmap: &mut Channels<'a, Repsiggen>,
+    blockmap: &mut Channels<'a, Token<Block<VT>, ST>>,
+    bound_blocks: &HashSet<u64>,
+    outputs: &mut TensorOutputs,
+) -> Result<(), GraphError> {
+    let block_vals = find_block_streams(&graph, funcs, bound_blocks);
+    let root_refs = find_root_refs(&graph);
+    let root_levels = root_levels(&graph);
+    for (index, operation) in graph.operators.into_iter().enumerate() {
+        let opref = OpRef::new(func, index, operation.op.as_ref());
+        let unsupported = |reason: String| GraphError::Unsupported {
+            op: opref.clone(),
+            reason,
+        };
+        let op = operation.op.ok_or_else(|| GraphError::MissingField {
+            op: opref.clone(),
+            field: "op",
+        })?;
+        match op {
+            Op::Broadcast(op) => match op.conn.as_ref().unwrap() {
+                broadcast::Conn::Crd(in_crd) => {
+                    let in_crd_id = in_crd.input.try_conv();
+                    let out_crd_ids = in_crd.outputs.iter().map(|id| id.try_conv());
+                    let receiver = crdmap.get_receiver(in_crd_id, builder);
+                    let mut broadcast = BroadcastContext::new(receiver);
+                    out_crd_ids
+                        .into_iter()
+                        .for_each(|id| broadcast.add_target(crdmap.get_sender(id, builder)));
+                    builder.add_child(broadcast);
+                }
+                broadcast::Conn::Ref(in_ref) => {
+                    let in_ref_id = in_ref.input.try_conv();
+                    let out_ref_ids = in_ref.outputs.iter().map(|id| id.try_conv());
+                    let receiver = refmap.get_receiver(in_ref_id, builder);
+                    let mut broadcast = BroadcastContext::new(receiver);
+                    out_ref_ids
+                        .into_iter()
+                        .for_each(|id| broadcast.add_target(refmap.get_sender(id, builder)));
+                    builder.add_child(broadcast);
+                }
+                broadcast::Conn::Val(in_val) if block_vals.contains(&in_val.input.try_conv()) => {
+                    let in_val_id = in_val.input.try_conv();
+                    let out_val_ids = in_val.outputs.iter().map(|id| id.try_conv());
+                    let receiver = blockmap.get_receiver(in_val_id, builder);
+                    let mut broadcast = BroadcastContext::new(receiver);
+                    out_val_ids
+                        .into_iter()
+                        .for_each(|id| broadcast.add_target(blockmap.get_sender(id, builder)));
+                    builder.add_child(broadcast);
+                }
+                broadcast::Conn::Val(in_val) => {
+                    let in_val_id = in_val.input.try_conv();
+                    let out_val_ids = in_val.outputs.iter().map(|id| id.try_conv());
+                    let receiver = valmap.get_receiver(in_val_id, builder);
+                    let mut broadcast = BroadcastContext::new(receiver);
+                    out_val_ids
+                        .into_iter()
+                        .for_each(|id| broadcast.add_target(valmap.get_sender(id, builder)));
+                    builder.add_child(broadcast);
+                }
+                broadcast::Conn::Repsig(in_repsig) => {
+                    let in_repsig_id = in_repsig.input.try_conv();
+                    let out_repsig_ids = in_repsig.outputs.iter().map(|id| id.try_conv());
+                    let receiver = repmap.get_receiver(in_repsig_id, builder);
+                    let mut broadcast = BroadcastContext::new(receiver);
+                    out_repsig_ids
+                        .into_iter()
+                        .for_each(|id| broadcast.add_target(repmap.get_sender(id, builder)));
+                    builder.add_child(broadcast);
+                }
+            },
+            Op::Joiner(op) => {
+                let (in_crds, in_refs): (Vec<_>, Vec<_>) = op
+                    .input_pairs
+                    .iter()
+                    .map(|pair| {
+                        let pair_crd = crdmap.get_receiver(get_crd_id(&pair.crd), builder);
+                        let pair_ref = refmap.get_receiver(get_ref_id(&pair.r#ref), builder);
+                        (pair_crd, pair_ref)
+                    })
+                    .unzip();
+                let mut out_refs: Vec<_> = op
+                    .output_refs
+                    .iter()
+                    .map(|out_ref| refmap.get_sender(get_ref_id(&Some(out_ref.clone())), builder))
+                    .collect();
+                let out_crd = crdmap.get_sender(get_crd_id(&op.output_crd), builder);
+
+                if in_crds.len() == 2 {
+                    let mut in_crds = in_crds.into_iter();
+                    let mut in_refs = in_refs.into_iter();
+                    let out_ref2 = out_refs.pop().unwrap();
+                    let out_ref1 = out_refs.pop().unwrap();
+                    let joiner_data = CrdJoinerData {
+                        in_crd1: in_crds.next().unwrap(),
+                        in_ref1: in_refs.next().unwrap(),
+                        in_crd2: in_crds.next().unwrap(),
+                        in_ref2: in_refs.next().unwrap(),
+                        out_ref1,
+                        out_ref2,
+                        out_crd,
+                    };
+
+                    match op.join_type() {
+                        joiner::Type::Intersect => builder.add_child(Intersect::new(joiner_data)),
+                        joiner::Type::Union => builder.add_child(Union::new(joiner_data)),
+                    };
+                } else {
+                    let joiner_data = CrdJoinerNData {
+                        in_crds,
+                        in_refs,
+                        out_refs,
+                        out_crd,
+                    };
+
+                    match op.join_type() {
+                        joiner::Type::Intersect => builder.add_child(IntersectN::new(joiner_data)),
+                        joiner::Type::Union => builder.add_child(UnionN::new(joiner_data)),
+                    };
+                }
+            }
+            Op::FiberLookup(op) => {
+                let in_ref = refmap.get_receiver(get_ref_id(&op.input_ref), builder);
+
+                let f_data = RdScanData {
+                    in_ref,
+                    out_crd: crdmap.get_sender(get_crd_id(&op.output_crd), builder),
+                    out_ref: refmap.get_sender(get_ref_id(&op.output_ref), builder),
+                };
+                if let Some(format) = data.format(&op.tensor, op.mode) {
+                    if format != op.format {
+                        return Err(unsupported(format!(
+                            "the graph reads it as {:?}, but the manifest stores {:?}",
+                            op.format, format
+                        )));
+                    }
+                }
+                if op.format == "compressed" {
+                    let seg_file = data.path(&op.tensor, TensorFile::Seg(op.mode));
+                    let seg: Vec<CT> = load(&opref, &seg_file)?;
+                    // Each root batch looks up its own fiber of the outermost level
+                    let num_fibers = seg.len().saturating_sub(1) as u64;
+                    if root_refs.contains(&get_ref_id(&op.input_ref)) {
+                        let past_end =
+                            (sam_options.root_batches.iter()).find(|&&batch| batch >= num_fibers);
+                        if let Some(batch) = past_end {
+                            return Err(GraphError::DataFile {
+                                op: opref.clone(),
+                                reason: format!(
+                                    "root batch {batch} is past the {num_fibers} fibers in {seg_file:?}"
+                                ),
+                            });
+                        }
+                    }
+                    let crd = load(&opref, &data.path(&op.tensor, TensorFile::Crd(op.mode)))?;
+                    let mut crs = CompressedCrdRdScan::new(f_data, seg, crd);
+                    crs.set_timings(sam_options.compressed_read_config);
+                    builder.add_child(crs);
+                } else if op.format == "tiled" {
+                    // Tiles are numbered from 0 with no gaps, one seg/crd pair per tile
+                    let mode = op.mode;
+                    let tile_seg = |tile| data.path(&op.tensor, TensorFile::TileSeg { tile, mode });
+                    let tile_crd = |tile| data.path(&op.tensor, TensorFile::TileCrd { tile, mode });
+                    let num_tiles = (0..)
+                        .take_while(|&tile| data_exists(&tile_seg(tile)))
+                        .count();
+                    if num_tiles == 0 {
+                        return Err(GraphError::DataFile {
+                            op: opref.clone(),
+                            reason: format!("no tiles found, expected {:?}", tile_seg(0)),
+                        });
+                    }
+                    let segs = (0..num_tiles)
+                        .map(|tile| load(&opref, &tile_seg(tile)))
+                        .collect::<Result<_, _>>()?;
+                    let crds = (0..num_tiles)
+                        .map(|tile| load(&opref, &tile_crd(tile)))
+                        .collect::<Result<_, _>>()?;
+                    let mut trs = TileRdScan::new(f_data, segs, crds, num_tiles);
+                    trs.set_timings(sam_options.tile_read_config);
+                    if num_tiles > 1 {
+                        // The Root sends a single Done, so root batch t reads tile t and the stops
+                        // between the batches switch tiles
+                        if sam_options.num_batches() != num_tiles {
+                            return Err(unsupported(format!(
+                                "{num_tiles} tiles, but {} root batches to read them",
+                                sam_options.num_batches()
+                            )));
+                        }
+                        let level = (root_levels.get(&get_ref_id(&op.input_ref)))
+                            .and_then(|&level| num::cast::<_, ST>(level))
+                            .ok_or_else(|| {
+                                unsupported(
+                                    "tiles need a fixed number of levels below the Root"
+                                        .to_string(),
+                                )
+                            })?;
+                        trs.set_tile_stop(level);
+                    }
+                    builder.add_child(trs);
+                } else {
+                    let size: CT = match data.mode_size(&op.tensor, op.mode) {
+                        Some(size) => num::cast(size).ok_or_else(|| {
+                            unsupported(format!("mode size {size} in {} coordinates", CT::DTYPE))
+                        })?,
+                        None => {
+                            let shape_file = data.path(&op.tensor, TensorFile::Shape);
+                            let shapes = load(&opref, &shape_file)?;
+                            let index: usize = op.mode.try_into().unwrap();
+                            shapes
+                                .get(index)
+                                .copied()
+                                .ok_or_else(|| GraphError::DataFile {
+                                    op: opref.clone(),
+                                    reason: format!("{shape_file:?} has no size for mode {index}"),
+                                })?
+                        }
+                    };
+                    builder.add_child(UncompressedCrdRdScan::new(f_data, size));
+                }
+            }
+            Op::FiberWrite(op) => {
+                let in_crd_id = get_crd_id(&op.input_crd);
+                let receiver = crdmap.get_receiver(in_crd_id, builder);
+                let wr_scan = CompressedWrScan::new(receiver);
+                outputs.add_fiber(
+                    op.tensor.clone(),
+                    op.mode,
+                    wr_scan.seg_arr.clone(),
+                    wr_scan.crd_arr.clone(),
+                );
+                outputs.add_marks(wr_scan.marks.clone());
+                builder.add_child(wr_scan);
+            }
+            Op::Repeat(op) => {
+                let (out_repsig, in_repsig) = repmap.new_channel(builder, None);
+
+                // Newer graphs repeat over a crd stream, older ones over a ref stream.
+                // Both carry the same fiber structure, which is all the signal generator looks at.
+                let input = match get_crd_id(&op.input_rep_crd) {
+                    0 => refmap.get_receiver(get_ref_id(&op.input_rep_ref), builder),
+                    in_rep_crd => crdmap.get_receiver(in_rep_crd, builder),
+                };
+                let repsig_data = RepSigGenData { input, out_repsig };
+
+                builder.add_child(RepeatSigGen::new(repsig_data));
+
+                let in_ref = refmap.get_receiver(get_ref_id(&op.input_ref), builder);
+
+                let rep_data = RepeatData {
+                    in_ref,
+                    in_repsig,
+                    out_ref: refmap.get_sender(get_ref_id(&op.output_ref), builder),
+                };
+                builder.add_child(Repeat::new(rep_data));
+            }
+            Op::Repeatsig(op) => {
+                let in_crd_id = get_crd_id(&op.input_crd);
+                let repsig_data = RepSigGenData {
+                    input: crdmap.get_receiver(in_crd_id, builder),
+                    out_repsig: repmap.get_sender(get_repsig_id(&op.output_rep_sig), builder),
+                };
+                builder.add_child(RepeatSigGen::new(repsig_data));
+            }
+            Op::Alu(op) => match op.conn.as_ref().unwrap() {
+                alu::Conn::Vals(val)
+                    if (val.inputs.iter()).any(|input| block_vals.contains(&input.try_conv())) =>
+                {
+                    // Every input of a blocked ALU has to carry blocks, scalars would come in on the wrong channel type
+                    let scalar_input = (val.inputs.iter())
+                        .map(|input| input.try_conv())
+                        .find(|input| !block_vals.contains(input));
+                    if let Some(input) = scalar_input {
+                        return Err(unsupported(format!(
+                            "val stream {input} is scalar, but other inputs carry blocks"
+                        )));
+                    }
+                    let in_vals = val
+                        .inputs
+                        .iter()
+                        .map(|input_val| blockmap.get_receiver(input_val.try_conv(), builder))
+                        .collect();
+                    let out_val_sender = blockmap.get_sender(get_val_id(&val.output), builder);
+                    let ops = op
+                        .stages
+                        .iter()
+                        .map(|stage| {
+                            VT::block_alu_op(stage.op()).ok_or_else(|| {
+                                unsupported(format!("blocked ALU op {:?}", stage.op()))
+                            })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let arities = ops.iter().map(ChainedOp::arity).collect();
+                    let operands = chain_operands(arities, stage_operands(&op), val.inputs.len())
+                        .map_err(unsupported)?;
+                    let stages = (ops.into_iter().zip(operands))
+                        .map(|(op, operands)| ChainedStage { op, operands })
+                        .collect();
+                    builder.add_child(make_chained_alu(in_vals, out_val_sender, stages));
+                }
+                alu::Conn::Vals(val) => {
+                    let ops: Vec<_> = (op.stages.iter())
+                        .map(|stage| match stage.op() {
+                            alu::AluOp::Max => num::cast(stage.scalar)
+                                .map(ValStage::ScalarMax)
+                                .ok_or_else(|| {
+                                    unsupported(format!(
+                                        "scalar {} on {} values",
+                                        stage.scalar,
+                                        VT::DTYPE
+                                    ))
+                                }),
+                            stage_op => VT::alu_op(stage_op)
+                                .map(ValStage::Chained)
+                                .ok_or_else(|| unsupported(format!("value ALU op {:?}", stage_op))),
+                        })
+                        .collect::<Result<_, _>>()?;
+                    let arities = ops.iter().map(ValStage::arity).collect();
+                    let operands = chain_operands(arities, stage_operands(&op), val.inputs.len())
+                        .map_err(unsupported)?;
+
+                    let in_vals = (val.inputs.iter())
+                        .map(|input_val| valmap.get_receiver(input_val.try_conv(), builder))
+                        .collect();
+                    let out_val_sender = valmap.get_sender(get_val_id(&val.output), builder);
+                    let stages = ops.into_iter().zip(operands).collect();
+                    build_val_chain(stages, in_vals, out_val_sender, valmap, builder)
+                        .map_err(unsupported)?;
+                }
+                alu::Conn::Crds(crd) => {
+                    // Coordinate ALUs aren't chained, they apply exactly one op
+                    let stage = match op.stages.as_slice() {
+                        [stage] => stage,
+                        stages => {
+                            return Err(unsupported(format!(
+                                "coordinate ALU with {} stages",
+                                stages.len()
+                            )))
+                        }
+                    };
+                    let mut in_crd_ids = crd.inputs.iter().map(|input_crd| input_crd.try_conv());
+                    if in_crd_ids.len() == 2 {
+                        let crd_op = match stage.op() {
+                            alu::AluOp::Min => CrdBinaryOp::Min,
+                            alu::AluOp::Max => CrdBinaryOp::Max,
+                            other => {
+                                return Err(unsupported(format!(
+                                    "binary coordinate op {:?}",
+                                    other
+                                )))
+                            }
+                        };
+                        let crd_alu_data = CrdBinaryAluData {
+                            in_crd1: crdmap.get_receiver(in_crd_ids.next().unwrap(), builder),
+                            in_crd2: crdmap.get_receiver(in_crd_ids.next().unwrap(), builder),
+                            out_crd: crdmap.get_sender(get_crd_id(&crd.output), builder),
+                        };
+                        builder.add_child(CrdBinaryAlu::new(crd_alu_data, crd_op));
+                    } else if in_crd_ids.len() == 1 {
+                        let bad_scalar = |why: &str| {
+                            unsupported(format!(
+                                "scalar {} on {} coordinates: {why}",
+                                stage.scalar,
+                                CT::DTYPE
+                            ))
+                        };
+                        if !num::cast::<_, f64>(stage.scalar).is_some_and(|scalar| scalar >= 0.0) {
+                            return Err(bad_scalar("coordinates can't go negative"));
+                        }
+                        let scalar: CT = num::cast(stage.scalar)
+                            .ok_or_else(|| bad_scalar("it doesn't fit the coordinate type"))?;
+                        let crd_op = match stage.op() {
+                            alu::AluOp::Add => CrdScalarOp::Add(scalar),
+                            alu::AluOp::Sub => CrdScalarOp::Sub(scalar),
+                            alu::AluOp::Mul => CrdScalarOp::Mul(scalar),
+                            alu::AluOp::Mod | alu::AluOp::Div if scalar.is_zero() => {
+                                return Err(bad_scalar("division by zero"))
+                            }
+                            alu::AluOp::Mod => CrdScalarOp::Mod(scalar),
+                            alu::AluOp::Div => CrdScalarOp::Div(scalar),
+                            other => return Err(unsupported(format!("coordinate op {:?}", other))),
+                        };
+                        let in_crd = crdmap.get_receiver(in_crd_ids.next().unwrap(), builder);
+                        let out_crd_sender = crdmap.get_sender(get_crd_id(&crd.output), builder);
+                        builder.add_child(CrdScalarAlu::new(in_crd, out_crd_sender, crd_op));
+                    } else {
+                        return Err(unsupported(format!(
+                            "coordinate ALU with {} inputs",
+                            in_crd_ids.len()
+                        )));
+                    }
+                }
+            },
+            Op::Reduce(op) if block_vals.contains(&get_val_id(&op.input_val)) => {
+                let reduce_data = ReduceData {
+                    in_val: blockmap.get_receiver(get_val_id(&op.input_val), builder),
+                    out_val: blockmap.get_sender(get_val_id(&op.output_val), builder),
+                };
+                builder.add_child(Reduce::new(reduce_data));
+            }
+            Op::Reduce(op) => {
+                let in_val_id = get_val_id(&op.input_val);
+                let reduce_data = ReduceData {
+                    in_val: valmap.get_receiver(in_val_id, builder),
+                    out_val: valmap.get_sender(get_val_id(&op.output_val), builder),
+                };
+                builder.add_child(Reduce::new(reduce_data));
+            }
+            Op::CoordHold(op) => {
+                let in_inner_crd = get_crd_id(&op.input_inner_crd);
+                let in_outer_crd = get_crd_id(&op.input_outer_crd);
+
+                let crd_hold_data = CrdManagerData {
+                    in_crd_inner: crdmap.get_receiver(in_inner_crd, builder),
+                    in_crd_outer: crdmap.get_receiver(in_outer_crd, builder),
+                    out_crd_inner: crdmap.get_sender(get_crd_id(&op.output_inner_crd), builder),
+                    out_crd_outer: crdmap.get_sender(get_crd_id(&op.output_outer_crd), builder),
+                };
+                builder.add_child(CrdHold::new(crd_hold_data));
+            }
+            Op::CoordDrop(op) => {
+                let in_inner_crd = get_crd_id(&op.input_inner_crd);
+                let in_outer_crd = get_crd_id(&op.input_outer_crd);
+
+                let crd_drop_data = CrdManagerData {
+                    in_crd_inner: crdmap.get_receiver(in_inner_crd, builder),
+                    in_crd_outer: crdmap.get_receiver(in_outer_crd, builder),
+                    out_crd_inner: crdmap.get_sender(get_crd_id(&op.output_inner_crd), builder),
+                    out_crd_outer: crdmap.get_sender(get_crd_id(&op.output_outer_crd), builder),
+                };
+                builder.add_child(CrdDrop::new(crd_drop_data));
+            }
+            Op::Array(op) => {
+                let in_ref = refmap.get_receiver(get_ref_id(&op.input_ref), builder);
+                let out_val_id = get_val_id(&op.output_val);
+                if let Some(dtype) = data.tensor(&op.tensor).and_then(|tensor| tensor.dtype) {
+                    if dtype.is_float() != VT::DTYPE.is_float() {
+                        return Err(unsupported(format!(
+                            "the manifest stores {dtype} values, but the graph runs on {}",
+                            VT::DTYPE
+                        )));
+                    }
+                }
+                let val_filename = data.path(&op.tensor, TensorFile::Vals);
+                if block_vals.contains(&out_val_id) {
+                    let blocks = PrimitiveType::<Block<VT>>::new()
+                        .parse(
+                            load(&opref, &val_filename)?,
+                            Some(op.stream_shape as usize),
+                            Some(op.blocked),
+                        )
+                        .map_err(|err| GraphError::DataFile {
+                            op: opref.clone(),
+                            reason: format!("{val_filename:?}: {err}"),
+                        })?;
+                    let array_data = ArrayData {
+                        in_ref,
+                        out_val: blockmap.get_sender(out_val_id, builder),
+                    };
+                    builder.add_child(Array::new(array_data, blocks));
+                } else {
+                    let vals = load(&opref, &val_filename)?;
+                    let array_data = ArrayData {
+                        in_ref,
+                        out_val: valmap.get_sender(out_val_id, builder),
+                    };
+                    builder.add_child(Array::new(array_data, vals));
+                }
+            }
+            Op::Spacc(op) => {
+                if block_vals.contains(&get_val_id(&op.input_val)) {
+                    return Err(unsupported("blocked values".to_string()));
+                }
+                let in_inner_crd = get_crd_id(&op.input_inner_crd);
+                let in_val_id = get_val_id(&op.input_val);
+
+                // Outer crds are listed innermost first, ending with the coordinate being reduced
+                let (in_reduce_crd, in_kept_crds) =
+                    op.input_outer_crds
+                        .split_last()
+                        .ok_or_else(|| GraphError::MissingField {
+                            op: opref.clone(),
+                            field: "input_outer_crds",
+                        })?;
+
+                if in_kept_crds.is_empty() {
+                    let spacc_data = Spacc1Data {
+                        in_crd_inner: crdmap.get_receiver(in_inner_crd, builder),
+                        in_crd_outer: crdmap.get_receiver(in_reduce_crd.try_conv(), builder),
+                        in_val: valmap.get_receiver(in_val_id, builder),
+                        out_crd_inner: crdmap.get_sender(get_crd_id(&op.output_inner_crd), builder),
+                        out_val: valmap.get_sender(get_val_id(&op.output_val), builder),
+                    };
+                    builder.add_child(Spacc1::new(spacc_data));
+                } else {
+                    if in_kept_crds.len() != op.output_outer_crds.len() {
+                        return Err(unsupported(format!(
+                            "{} kept outer crds but {} outer outputs",
+                            in_kept_crds.len(),
+                            op.output_outer_crds.len()
+                        )));
+                    }
+                    let mut in_crds: Vec<_> = in_kept_crds
+                        .iter()
+                        .rev()
+                        .map(|crd| crdmap.get_receiver(crd.try_conv(), builder))
+                        .collect();
+                    in_crds.push(crdmap.get_receiver(in_inner_crd, builder));
+                    let mut out_crds: Vec<_> = op
+                        .output_outer_crds
+                        .iter()
+                        .rev()
+                        .map(|crd| crdmap.get_sender(crd.try_conv(), builder))
+                        .collect();
+                    out_crds.push(crdmap.get_sender(get_crd_id(&op.output_inner_crd), builder));
+
+                    let spacc_data = SpaccNData {
+                        in_val: valmap.get_receiver(in_val_id, builder),
+                        in_crd_reduce: crdmap.get_receiver(in_reduce_crd.try_conv(), builder),
+                        in_crds,
+                        out_val: valmap.get_sender(get_val_id(&op.output_val), builder),
+                        out_crds,
+                    };
+                    builder.add_child(SpaccN::new(spacc_data));
+                }
+            }
+            Op::ValWrite(op) if block_vals.contains(&get_val_id(&op.input_val)) => {
+                let val_receiver = blockmap.get_receiver(get_val_id(&op.input_val), builder);
+                let wr_scan = ValsWrScan::new(val_receiver);
+                outputs.add_block_vals(op.tensor.clone(), wr_scan.out_val.clone());
+                outputs.add_marks(wr_scan.marks.clone());
+                builder.add_child(wr_scan);
+            }
+            Op::ValWrite(op) => {
+                let in_val_id = get_val_id(&op.input_val);
+                let val_receiver = valmap.get_receiver(in_val_id, builder);
+                let wr_scan = ValsWrScan::new(val_receiver);
+                outputs.add_vals(op.tensor.clone(), wr_scan.out_val.clone());
+                outputs.add_marks(wr_scan.marks.clone());
+                builder.add_child(wr_scan);
+            }
+            Op::CoordMask(op) => {
+                let params: Vec<CT> = op
+                    .params
+                    .iter()
+                    .map(|param| num::cast(*param))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| unsupported(format!("mask parameters {:?}", op.params)))?;
+                let predicate =
+                    MaskPredicate::from_name(&op.predicate, &params).ok_or_else(|| {
+                        unsupported(format!(
+                            "coord mask predicate {:?} with params {:?}",
+                            op.predicate, op.params
+                        ))
+                    })?;
+
+                let crd_mask_data = CrdMaskData {
+                    in_crd_inner: crdmap.get_receiver(get_crd_id(&op.input_inner_crd), builder),
+                    in_crd_outer: crdmap.get_receiver(get_crd_id(&op.input_outer_crd), builder),
+                    in_ref_inner: refmap.get_receiver(get_ref_id(&op.input_ref), builder),
+                    out_crd_inner: crdmap.get_sender(get_crd_id(&op.output_inner_crd), builder),
+                    out_crd_outer: crdmap.get_sender(get_crd_id(&op.output_outer_crd), builder),
+                    out_ref_inner: refmap.get_sender(get_ref_id(&op.output_ref), builder),
+                };
+                builder.add_child(CrdMask::new(crd_mask_data, move |outer, inner| {
+                    predicate.is_masked(&outer, &inner)
+                }));
+            }
+            Op::Func(op) => {
+                let Some(subgraph) = funcs.get(&op.name).cloned() else {
+                    let custom =
+                        find_custom_op(&op.name).ok_or_else(|| GraphError::UnknownFunction {
+                            op: opref.clone(),
+                            name: op.name.clone(),
+                        })?;
+                    let build = custom.builder::<VT, CT>().ok_or_else(|| {
+                        unsupported(format!(
+                            "custom op {:?} has no builder for {} values and {} coordinates",
+                            op.name,
+                            VT::DTYPE,
+                            CT::DTYPE
+                        ))
+                    })?;
+                    build(CustomOpArgs {
+                        name: &op.name,
+                        data,
+                        sam_options,
+                        builder,
+                        ref_bindings: &op.ref_bindings,
+                        crd_bindings: &op.crd_bindings,
+                        val_bindings: &op.val_bindings,
+                        repsig_bindings: &op.repsig_bindings,
+                        refmap,
+                        crdmap,
+                        valmap,
+                        repmap,
+                    })
+                    .map_err(unsupported)?;
+                    continue;
+                };
+
+                // Each instance gets its own stream-ID namespace, only the bound streams are shared with the caller.
+                let mut sub_refmap = refmap.scoped();
+                let mut sub_crdmap = crdmap.scoped();
+                let mut sub_valmap = valmap.scoped();
+                let mut sub_repmap = repmap.scoped();
+                let mut sub_blockmap = blockmap.scoped();
+                // Blocked values stay blocked on both sides of the call
+                let sub_bound_blocks = (op.val_bindings.iter())
+                    .filter(|(_, outer)| block_vals.contains(outer))
+                    .map(|(inner, _)| *inner)
+                    .collect();
+                op.ref_bindings
+                    .iter()
+                    .for_each(|(inner, outer)| refmap.transfer(*outer, &mut sub_refmap, *inner));
+                op.crd_bindings
+                    .iter()
+                    .for_each(|(inner, outer)| crdmap.transfer(*outer, &mut sub_crdmap, *inner));
+                op.val_bindings.iter().for_each(|(inner, outer)| {
+                    valmap.transfer(*outer, &mut sub_valmap, *inner);
+                    blockmap.transfer(*outer, &mut sub_blockmap, *inner);
+                });
+                op.repsig_bindings
+                    .iter()
+                    .for_each(|(inner, outer)| repmap.transfer(*outer, &mut sub_repmap, *inner));
+
+                build_graph(
+                    subgraph,
+                    Some(&op.name),
+                    funcs,
+                    data,
+                    sam_options,
+                    builder,
+                    &mut sub_refmap,
+                    &mut sub_crdmap,
+                    &mut sub_valmap,
+                    &mut sub_repmap,
+                    &mut sub_blockmap,
+                    &sub_bound_blocks,
+                    outputs,
+                )?;
+
+                op.ref_bindings
+                    .iter()
+                    .for_each(|(inner, outer)| sub_refmap.transfer(*inner, refmap, *outer));
+                op.crd_bindings
+                    .iter()
+                    .for_each(|(inner, outer)| sub_crdmap.transfer(*inner, crdmap, *outer));
+                op.val_bindings.iter().for_each(|(inner, outer)| {
+                    sub_valmap.transfer(*inner, valmap, *outer);
+                    sub_blockmap.transfer(*inner, blockmap, *outer);
+                });
+                op.repsig_bindings
+                    .iter()
+                    .for_each(|(inner, outer)| sub_repmap.transfer(*inner, repmap, *outer));
+            }
+            Op::Root(op) => {
+                let out_ref_id = get_ref_id(&op.output_ref);
+
+                let root_sender = refmap.get_sender(out_ref_id, builder);
+                // One root ref per batch, separated by stops; a single batch is the usual `0, D`
+                let batches = match sam_options.root_batches.as_slice() {
+                    [] => vec![0],
+                    batches => batches.to_vec(),
+                };
+                let batches: Vec<CT> = (batches.iter())
+                    .map(|batch| num::cast(*batch))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| {
+                        unsupported(format!(
+                            "root batches {batches:?} in {} coordinates",
+                            CT::DTYPE
+                        ))
+                    })?;
+                let mut root_tokens: Vec<Token<CT, ST>> = batches
+                    .into_iter()
+                    .flat_map(|batch| [Token::Stop(0), Token::Val(batch)])
+                    .skip(1)
+                    .collect();
+                root_tokens.push(Token::Done);
+                builder.add_child(GeneratorContext::new(
+                    move || root_tokens.clone().into_iter(),
+                    root_sender,
+                ));
+                // root_receiver
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The operands each stage of `op` lists. Stage inputs count the ALU's input streams first, then the
+/// results of the earlier stages.
+fn stage_operands(op: &Alu) -> Vec<Vec<usize>> {
+    (op.stages.iter())
+        .map(|stage| stage.inputs.iter().map(|input| *input as usize).collect())
+        .collect()
+}
+
+/// The operands of each stage of an ALU chain, falling back to a left fold when no stage lists any.
+fn chain_operands(
+    arities: Vec<usize>,
+    listed: Vec<Vec<usize>>,
+    num_inputs: usize,
+) -> Result<Vec<Vec<usize>>, String> {
+    let operands = match listed.iter().all(Vec::is_empty) {
+        true => left_fold_operands(arities.iter().copied(), num_inputs),
+        false => listed,
+    };
+    let shape: Vec<_> = arities.into_iter().zip(operands.iter().cloned()).collect();
+    check_chain(num_inputs, &shape).map_err(|err| format!("ALU chain {shape:?}: {err}"))?;
+    Ok(operands)
+}
+
+/// A stage of a value ALU. ALUOps can't carry a constant, so a max against a scalar runs in a
+/// UnaryMax between the PCUs of the rest of the chain.
+enum ValStage<VT> {
+    Chained(ChainedOp<Token<VT, ST>>),
+    ScalarMax(VT),
+}
+
+impl<VT> ValStage<VT> {
+    fn arity(&self) -> usize {
+        match self {
+            ValStage::Chained(op) => op.arity(),
+            ValStage::ScalarMax(_) => 1,
+        }
+    }
+}
+
+/// Builds a value ALU chain as PCUs for the runs of chained stages, joined by the UnaryMax of each
+/// scalar max. Every value crosses a UnaryMax through a single channel, so a value can't be read on
+/// both sides of one.
+fn build_val_chain<'a, VT: ValueElement>(
+    stages: Vec<(ValStage<VT>, Vec<usize>)>,
+    in_vals: Vec<Receiver<Token<VT, ST>>>,
+    out_val: Sender<Token<VT, ST>>,
+    valmap: &mut Channels<'a, Token<VT, ST>>,
+    builder: &mut ProgramBuilder<'a>,
+) -> Result<(), String> {
+    let num_inputs = in_vals.len();
+    let num_stages = stages.len();
+    let mut available: HashMap<usize, Receiver<Token<VT, ST>>> =
+        in_vals.into_iter().enumerate().collect();
+    let take = |value: usize, available: &mut HashMap<usize, Receiver<Token<VT, ST>>>| {
+        available.remove(&value).ok_or_else(|| {
+            format!("value {value} can't be passed on to more than one stage past a scalar max")
+        })
+    };
+
+    // Each result leaving a PCU or UnaryMax gets a channel, except the chain's output
+    let is_scalar_max =
+        |stage: usize| matches!(stages.get(stage), Some((ValStage::ScalarMax(_), _)));
+    let mut senders = HashMap::new();
+    for stage in 0..num_stages - 1 {
+        if is_scalar_max(stage) || is_scalar_max(stage + 1) {
+            let (snd, rcv) = valmap.new_channel(builder, None);
+            senders.insert(stage, snd);
+            available.insert(num_inputs + stage, rcv);
+        }
+    }
+    senders.insert(num_stages - 1, out_val);
+
+    let mut stages = stages.into_iter().enumerate().peekable();
+    while let Some((first, (stage, operands))) = stages.next() {
+        let op = match stage {
+            ValStage::ScalarMax(scalar) => {
+                let in_val = take(operands[0], &mut available)?;
+                builder.add_child(UnaryMax::new(
+                    in_val,
+                    senders.remove(&first).unwrap(),
+                    scalar,
+                ));
+                continue;
+            }
+            ValStage::Chained(op) => op,
+        };
+        let mut run = vec![(op, operands)];
+        while let Some((_, (ValStage::Chained(op), operands))) =
+            stages.next_if(|(_, (stage, _))| matches!(stage, ValStage::Chained(_)))
+        {
+            run.push((op, operands));
+        }
+        let last = first + run.len() - 1;
+
+        // Renumber the run's values: what it reads from outside first, then its own results
+        let mut outside: Vec<usize> = (run.iter())
+            .flat_map(|(_, operands)| operands.iter().copied())
+            .filter(|&value| value < num_inputs + first)
+            .collect();
+        outside.sort_unstable();
+        outside.dedup();
+        let local = |value: usize| match outside.binary_search(&value) {
+            Ok(index) => index,
+            Err(_) => outside.len() + value - num_inputs - first,
+        };
+        let run: Vec<_> = (run.into_iter())
+            .map(|(op, operands)| ChainedStage {
+                op,
+                operands: operands.into_iter().map(local).collect(),
+            })
+            .collect();
+        let shape: Vec<_> = (run.iter())
+            .map(|stage| (stage.op.arity(), stage.operands.clone()))
+            .collect();
+        check_chain(outside.len(), &shape)
+            .map_err(|err| format!("stages {first} to {last}, split off by a scalar max: {err}"))?;
+
+        let in_vals = (outside.iter())
+            .map(|&value| take(value, &mut available))
+            .collect::<Result<_, _>>()?;
+        builder.add_child(make_chained_alu(
+            in_vals,
+            senders.remove(&last).unwrap(),
+            run,
+        ));
+    }
+    Ok(())
+}
+
+/// The ref streams carrying the root refs, straight from a Root or through broadcasts.
+fn find_root_refs(graph: &ProgramGraph) -> HashSet<u64> {
+    let mut root_refs = HashSet::new();
+    loop {
+        let num_found = root_refs.len();
+        for operation in &graph.operators {
+            match operation.op.as_ref() {
+                Some(Op::Root(op)) => {
+                    root_refs.insert(get_ref_id(&op.output_ref));
+                }
+                Some(Op::Broadcast(op)) => {
+                    if let Some(broadcast::Conn::Ref(rf)) = op.conn.as_ref() {
+                        if root_refs.contains(&rf.input.try_conv()) {
+                            root_refs.extend(rf.outputs.iter().map(|output| output.try_conv()));
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        if root_refs.len() == num_found {
+            return root_refs;
+        }
+    }
+}
+
+/// Loads a seg/crd/vals file for `op`, reporting missing or malformed files as graph errors.
+fn load<T: DataElement>(op: &OpRef, path: &Path) -> Result<Vec<T>, GraphError> {
+    read_data(path).map_err(|err| GraphError::DataFile {
+        op: op.clone(),
+        reason: err.to_string(),
+    })
+}
+
+pub(super) fn parse_proto<'a, VT: ValueElement, CT: CrdElement>(
+    comal_graph: ComalGraph,
+    base_path: PathBuf,
+    sam_options: SamOptions,
+) -> Result<(ProgramBuilder<'a>, TensorOutputs), GraphError> {
+    // The whole program is built here, so every stream has to be closed off inside the graph
+    validate(&comal_graph, |_, _| false, true)?;
+    let mut builder = ProgramBuilder::default();
+    let mut outputs = TensorOutputs::default();
+    build_from_proto::<VT, CT>(
+        comal_graph,
+        base_path,
+        sam_options,
+        &mut builder,
+        &mut Default::default(),
+        &mut Default::default(),
+        &mut Default::default(),
+        &mut Default::default(),
+        &mut outputs,
+    )?;
+    Ok((builder, outputs))
+}
+
+#[cfg(test)]
+mod tests {
+    use dam::simulation::{InitializationOptions, ProgramBuilder, RunOptions};
+    use dam::templates::ops::{ALUAddOp, ALUMulOp};
+    use dam::utility_contexts::{CheckerContext, GeneratorContext};
+
+    use super::{build_val_chain, ValStage};
+    use crate::cli_common::{CrdType, SamOptions, ValueType};
+    use crate::config::manifest::DataDir;
+    use crate::proto_driver::graph_file::{decode_graph, GraphFormat};
+    use crate::proto_driver::validate::GraphError;
+    use crate::proto_driver::{parse_proto, Channels};
+    use crate::templates::alu::ChainedOp;
+    use crate::templates::primitive::{ALUReluOp, Token};
+    use crate::token_vec;
+    use crate::utils::scratch::ScratchDir;
+
+    // Root -> B_i lookup -> B vals, written back out as X
+    const COPY_GRAPH: &str = r#"{
+        "graph": {
+            "name": "main",
+            "operators": [
+                {"op": {"Root": {"output_ref": {"id": {"id": 1}}}}},
+                {"op": {"FiberLookup": {
+                    "input_ref": {"id": {"id": 1}},
+                    "output_crd": {"id": {"id": 2}},
+                    "output_ref": {"id": {"id": 3}},
+                    "tensor": "B", "mode": 0, "format": "compressed"
+                }}},
+                {"op": {"FiberWrite": {"input_crd": {"id": {"id": 2}}, "tensor": "X", "mode": 0}}},
+                {"op": {"Array": {
+                    "input_ref": {"id": {"id": 3}},
+                    "output_val": {"id": {"id": 4}},
+                    "tensor": "B"
+                }}},
+                {"op": {"ValWrite": {"input_val": {"id": {"id": 4}}, "tensor": "X"}}}
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn wide_types_test() {
+        // A coordinate past u32 and a value past i32, so only i64 values with u64 coordinates hold them
+        let dir = ScratchDir::new("wide_types");
+        for tensor in ["B", "X"] {
+            dir.write(&format!("tensor_{tensor}_mode_0_seg"), "0\n2\n");
+            dir.write(&format!("tensor_{tensor}_mode_0_crd"), "3\n5000000000\n");
+            dir.write(&format!("tensor_{tensor}_mode_vals"), "-3\n9000000000\n");
+        }
+        let sam_options = SamOptions {
+            value_type: ValueType::I64,
+            crd_type: CrdType::U64,
+            ..Default::default()
+        };
+        let graph = decode_graph(COPY_GRAPH.as_bytes(), GraphFormat::Json).unwrap();
+        let (builder, outputs) = parse_proto(graph, dir.path().into(), sam_options).unwrap();
+        let executed = builder
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+
+        let data = DataDir::open(dir.path()).unwrap();
+        assert_eq!(outputs.check_against_dir(&data, 0.0), vec![]);
+    }
+
+    #[test]
+    fn missing_data_test() {
+        let dir = ScratchDir::new("missing_data");
+        let graph = decode_graph(COPY_GRAPH.as_bytes(), GraphFormat::Json).unwrap();
+        assert!(matches!(
+            parse_proto(graph, dir.path().into(), SamOptions::default()),
+            Err(GraphError::DataFile { op, .. }) if op.kind == "FiberLookup"
+        ));
+
+        dir.write("tensor_B_mode_0_seg", "0\n2\n");
+        dir.write("tensor_B_mode_0_crd", "0\nx\n");
+        let graph = decode_graph(COPY_GRAPH.as_bytes(), GraphFormat::Json).unwrap();
+        assert!(matches!(
+            parse_proto(graph, dir.path().into(), SamOptions::default()),
+            Err(GraphError::DataFile { reason, .. }) if reason.contains("line 2")
+        ));
+
+        // B has a single fiber at the outermost level, so there is no second batch to run
+        dir.write("tensor_B_mode_0_crd", "0\n1\n");
+        let sam_options = SamOptions {
+            root_batches: vec![0, 1],
+            ..Default::default()
+        };
+        let graph = decode_graph(COPY_GRAPH.as_bytes(), GraphFormat::Json).unwrap();
+        assert!(matches!(
+            parse_proto(graph, dir.path().into(), sam_options),
+            Err(GraphError::DataFile { reason, .. }) if reason.contains("root batch 1")
+        ));
+
+        // A tiled lookup needs at least its first tile
+        let graph = COPY_GRAPH.replace(r#""format": "compressed""#, r#""format": "tiled""#);
+        let graph = decode_graph(graph.as_bytes(), GraphFormat::Json).unwrap();
+        assert!(matches!(
+            parse_proto(graph, dir.path().into(), SamOptions::default()),
+            Err(GraphError::DataFile { reason, .. }) if reason.contains("no tiles")
+        ));
+    }
+
+    #[test]
+    fn tiled_lookup_test() {
+        let dir = ScratchDir::new("tiled_lookup");
+        dir.write("tensor_B_tile_0_mode_0_seg", "0\n2\n");
+        dir.write("tensor_B_tile_0_mode_0_crd", "0\n3\n");
+        dir.write("tensor_B_tile_1_mode_0_seg", "0\n1\n");
+        dir.write("tensor_B_tile_1_mode_0_crd", "5\n");
+        dir.write("tensor_B_mode_vals", "1\n2\n");
+        // Each root batch reads one tile, and X gets one fiber per tile
+        dir.write("tensor_X_mode_0_seg", "0\n2\n3\n");
+        dir.write("tensor_X_mode_0_crd", "0\n3\n5\n");
+        dir.write("tensor_X_mode_vals", "1\n2\n1\n");
+
+        let graph = COPY_GRAPH.replace(r#""format": "compressed""#, r#""format": "tiled""#);
+        let sam_options = SamOptions {
+            root_batches: vec![0, 0],
+            ..Default::default()
+        };
+        let decoded = decode_graph(graph.as_bytes(), GraphFormat::Json).unwrap();
+        let (builder, outputs) = parse_proto(decoded, dir.path().into(), sam_options).unwrap();
+        let executed = builder
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+
+        let data = DataDir::open(dir.path()).unwrap();
+        assert_eq!(outputs.check_against_dir(&data, 0.0), vec![]);
+
+        // Without a batch per tile, the later tiles would never be read
+        let decoded = decode_graph(graph.as_bytes(), GraphFormat::Json).unwrap();
+        assert!(matches!(
+            parse_proto(decoded, dir.path().into(), SamOptions::default()),
+            Err(GraphError::Unsupported { reason, .. }) if reason.contains("2 tiles")
+        ));
+    }
+
+    // Root -> B_i lookup -> B_j lookup -> B vals, written back out as X
+    const COPY_2D_GRAPH: &str = r#"{
+        "graph": {
+            "name": "main",
+            "operators": [
+                {"op": {"Root": {"output_ref": {"id": {"id": 1}}}}},
+                {"op": {"FiberLookup": {
+                    "input_ref": {"id": {"id": 1}},
+                    "output_crd": {"id": {"id": 2}},
+                    "output_ref": {"id": {"id": 3}},
+                    "tensor": "B", "mode": 0, "format": "compressed"
+                }}},
+                {"op": {"FiberWrite": {"input_crd": {"id": {"id": 2}}, "tensor": "X", "mode": 0}}},
+                {"op": {"FiberLookup": {
+                    "input_ref": {"id": {"id": 3}},
+                    "output_crd": {"id": {"id": 5}},
+                    "output_ref": {"id": {"id": 6}},
+                    "tensor": "B", "mode": 1, "format": "compressed"
+                }}},
+                {"op": {"FiberWrite": {"input_crd": {"id": {"id": 5}}, "tensor": "X", "mode": 1}}},
+                {"op": {"Array": {
+                    "input_ref": {"id": {"id": 6}},
+                    "output_val": {"id": {"id": 7}},
+                    "tensor": "B"
+                }}},
+                {"op": {"ValWrite": {"input_val": {"id": {"id": 7}}, "tensor": "X"}}}
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn parallelize_e2e_test() {
+        // Row 1 is empty, so the lanes see fibers of different lengths
+        let dir = ScratchDir::new("parallelize_e2e");
+        for tensor in ["B", "X"] {
+            dir.write(&format!("tensor_{tensor}_mode_0_seg"), "0\n3\n");
+            dir.write(&format!("tensor_{tensor}_mode_0_crd"), "0\n1\n2\n");
+            dir.write(&format!("tensor_{tensor}_mode_1_seg"), "0\n2\n2\n3\n");
+            dir.write(&format!("tensor_{tensor}_mode_1_crd"), "0\n2\n1\n");
+            dir.write(&format!("tensor_{tensor}_mode_vals"), "1\n2\n3\n");
+        }
+        let data = DataDir::open(dir.path()).unwrap();
+
+        // Split at the root, at the rows, and at the nonzeros, against the single-lane run
+        for parallelize in [None, Some((1, 2)), Some((3, 2)), Some((3, 3)), Some((6, 2))] {
+            let sam_options = SamOptions {
+                parallelize,
+                ..Default::default()
+            };
+            let graph = decode_graph(COPY_2D_GRAPH.as_bytes(), GraphFormat::Json).unwrap();
+            let (builder, outputs) = parse_proto(graph, dir.path().into(), sam_options).unwrap();
+            let executed = builder
+                .initialize(InitializationOptions::default())
+                .unwrap()
+                .run(RunOptions::default());
+            dbg!(executed.elapsed_cycles());
+
+            assert_eq!(
+                outputs.check_against_dir(&data, 0.0),
+                vec![],
+                "parallelize {parallelize:?}"
+            );
+        }
+    }
+
+    // Two calls of a B_i lookup + B vals function, one written out as X and the other as Y. Both
+    // bodies use the same inner stream IDs.
+    const FUNC_GRAPH: &str = r#"{
+        "graph": {
+            "name": "main",
+            "operators": [
+                {"op": {"Root": {"output_ref": {"id": {"id": 1}}}}},
+                {"op": {"Broadcast": {"conn": {"Ref": {
+                    "input": {"id": {"id": 1}},
+                    "outputs": [{"id": {"id": 10}}, {"id": {"id": 11}}]
+                }}}}},
+                {"op": {"Func": {
+                    "name": "copy",
+                    "ref_bindings": {"1": 10},
+                    "crd_bindings": {"2": 20},
+                    "val_bindings": {"4": 21}
+                }}},
+                {"op": {"Func": {
+                    "name": "copy",
+                    "ref_bindings": {"1": 11},
+                    "crd_bindings": {"2": 30},
+                    "val_bindings": {"4": 31}
+                }}},
+                {"op": {"FiberWrite": {"input_crd": {"id": {"id": 20}}, "tensor": "X", "mode": 0}}},
+                {"op": {"ValWrite": {"input_val": {"id": {"id": 21}}, "tensor": "X"}}},
+                {"op": {"FiberWrite": {"input_crd": {"id": {"id": 30}}, "tensor": "Y", "mode": 0}}},
+                {"op": {"ValWrite": {"input_val": {"id": {"id": 31}}, "tensor": "Y"}}}
+            ]
+        },
+        "funcs": [{
+            "name": "copy",
+            "operators": [
+                {"op": {"FiberLookup": {
+                    "input_ref": {"id": {"id": 1}},
+                    "output_crd": {"id": {"id": 2}},
+                    "output_ref": {"id": {"id": 3}},
+                    "tensor": "B", "mode": 0, "format": "compressed"
+                }}},
+                {"op": {"Array": {
+                    "input_ref": {"id": {"id": 3}},
+                    "output_val": {"id": {"id": 4}},
+                    "tensor": "B"
+                }}}
+            ]
+        }]
+    }"#;
+
+    #[test]
+    fn func_test() {
+        let dir = ScratchDir::new("func");
+        for tensor in ["B", "X", "Y"] {
+            dir.write(&format!("tensor_{tensor}_mode_0_seg"), "0\n3\n");
+            dir.write(&format!("tensor_{tensor}_mode_0_crd"), "0\n2\n5\n");
+            dir.write(&format!("tensor_{tensor}_mode_vals"), "1\n2\n3\n");
+        }
+        // Ref, crd and val bindings all carry their streams in or out of both instances
+        let graph = decode_graph(FUNC_GRAPH.as_bytes(), GraphFormat::Json).unwrap();
+        let (builder, outputs) =
+            parse_proto(graph, dir.path().into(), SamOptions::default()).unwrap();
+        let executed = builder
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+
+        let data = DataDir::open(dir.path()).unwrap();
+        assert_eq!(outputs.check_against_dir(&data, 0.0), vec![]);
+
+        // Without its val binding, the second call would leave the body's Array writing nowhere
+        let graph = FUNC_GRAPH.replacen(r#""val_bindings": {"4": 31}"#, r#""val_bindings": {}"#, 1);
+        let graph = decode_graph(graph.as_bytes(), GraphFormat::Json).unwrap();
+        assert!(matches!(
+            parse_proto(graph, dir.path().into(), SamOptions::default()),
+            Err(GraphError::UnboundPort { id: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn blocked_func_test() {
+        let dir = ScratchDir::new("blocked_func");
+        for tensor in ["B", "X"] {
+            dir.write(&format!("tensor_{tensor}_mode_0_seg"), "0\n2\n");
+            dir.write(&format!("tensor_{tensor}_mode_0_crd"), "0\n3\n");
+            dir.write(
+                &format!("tensor_{tensor}_mode_vals"),
+                "1\n2\n3\n4\n5\n6\n7\n8\n",
+            );
+        }
+        // The caller reads 2x2 blocks, and only the function body writes them
+        let graph = r#"{
+            "graph": {
+                "name": "main",
+                "operators": [
+                    {"op": {"Root": {"output_ref": {"id": {"id": 1}}}}},
+                    {"op": {"FiberLookup": {
+                        "input_ref": {"id": {"id": 1}},
+                        "output_crd": {"id": {"id": 2}},
+                        "output_ref": {"id": {"id": 3}},
+                        "tensor": "B", "mode": 0, "format": "compressed"
+                    }}},
+                    {"op": {"Array": {
+                        "input_ref": {"id": {"id": 3}},
+                        "output_val": {"id": {"id": 4}},
+                        "tensor": "B", "blocked": true, "stream_shape": 2
+                    }}},
+                    {"op": {"FiberWrite": {"input_crd": {"id": {"id": 2}}, "tensor": "X", "mode": 0}}},
+                    {"op": {"Func": {"name": "write", "val_bindings": {"1": 4}}}}
+                ]
+            },
+            "funcs": [{
+                "name": "write",
+                "operators": [
+                    {"op": {"ValWrite": {"input_val": {"id": {"id": 1}}, "tensor": "X"}}}
+                ]
+            }]
+        }"#;
+        let graph = decode_graph(graph.as_bytes(), GraphFormat::Json).unwrap();
+        let (builder, outputs) =
+            parse_proto(graph, dir.path().into(), SamOptions::default()).unwrap();
+        let executed = builder
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+
+        let data = DataDir::open(dir.path()).unwrap();
+        assert_eq!(outputs.check_against_dir(&data, 0.0), vec![]);
+    }
+
+    #[test]
+    fn mixed_block_alu_test() {
+        let dir = ScratchDir::new("mixed_block_alu");
+        // B is read as blocks and C as scalars, so their product has no single channel type
+        let graph = r#"{
+            "graph": {
+                "name": "main",
+                "operators": [
+                    {"op": {"Alu": {
+                        "conn": {"Vals": {
+                            "inputs": [{"id": {"id": 4}}, {"id": {"id": 7}}],
+                            "output": {"id": {"id": 8}}
+                        }},
+                        "stages": [{}]
+                    }}},
+                    {"op": {"Root": {"output_ref": {"id": {"id": 1}}}}},
+                    {"op": {"FiberLookup": {
+                        "input_ref": {"id": {"id": 1}},
+                        "output_crd": {"id": {"id": 2}},
+                        "output_ref": {"id": {"id": 3}},
+                        "tensor": "B", "mode": 0, "format": "compressed"
+                    }}},
+                    {"op": {"Broadcast": {"conn": {"Ref": {
+                        "input": {"id": {"id": 3}},
+                        "outputs": [{"id": {"id": 5}}, {"id": {"id": 6}}]
+                    }}}}},
+                    {"op": {"Array": {
+                        "input_ref": {"id": {"id": 5}},
+                        "output_val": {"id": {"id": 4}},
+                        "tensor": "B", "blocked": true, "stream_shape": 2
+                    }}},
+                    {"op": {"Array": {
+                        "input_ref": {"id": {"id": 6}},
+                        "output_val": {"id": {"id": 7}},
+                        "tensor": "C"
+                    }}},
+                    {"op": {"FiberWrite": {"input_crd": {"id": {"id": 2}}, "tensor": "X", "mode": 0}}},
+                    {"op": {"ValWrite": {"input_val": {"id": {"id": 8}}, "tensor": "X"}}}
+                ]
+            }
+        }"#;
+        let graph = decode_graph(graph.as_bytes(), GraphFormat::Json).unwrap();
+        assert!(matches!(
+            parse_proto(graph, dir.path().into(), SamOptions::default()),
+            Err(GraphError::Unsupported { op, reason })
+                if op.kind == "Alu" && reason.contains("val stream 7")
+        ));
+    }
+
+    #[test]
+    fn scalar_max_chain_test() {
+        let mut builder = ProgramBuilder::default();
+        let mut valmap = Channels::default();
+        let (a_send, a_recv) = builder.unbounded::<Token<f32, u32>>();
+        let (b_send, b_recv) = builder.unbounded::<Token<f32, u32>>();
+        let (c_send, c_recv) = builder.unbounded::<Token<f32, u32>>();
+        let (out_send, out_recv) = builder.unbounded::<Token<f32, u32>>();
+
+        // relu(max(a * b, 0.5) + c), with the max between two PCUs
+        let stages = vec![
+            (ValStage::Chained(ChainedOp::Binary(ALUMulOp())), vec![0, 1]),
+            (ValStage::ScalarMax(0.5), vec![3]),
+            (ValStage::Chained(ChainedOp::Binary(ALUAddOp())), vec![4, 2]),
+            (ValStage::Chained(ChainedOp::Unary(ALUReluOp())), vec![5]),
+        ];
+        let in_vals = vec![a_recv, b_recv, c_recv];
+        build_val_chain(stages, in_vals, out_send, &mut valmap, &mut builder).unwrap();
+
+        builder.add_child(GeneratorContext::new(
+            || token_vec!(f32; u32; 1.0, 2.0, 0.5, "S0", "D").into_iter(),
+            a_send,
+        ));
+        builder.add_child(GeneratorContext::new(
+            || token_vec!(f32; u32; 2.0, 0.1, 4.0, "S0", "D").into_iter(),
+            b_send,
+        ));
+        builder.add_child(GeneratorContext::new(
+            || token_vec!(f32; u32; -1.0, 0.0, -3.0, "S0", "D").into_iter(),
+            c_send,
+        ));
+        builder.add_child(CheckerContext::new(
+            || token_vec!(f32; u32; 1.0, 0.5, 0.0, "S0", "D").into_iter(),
+            out_recv,
+        ));
+        let executed = builder
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+
+        // Input 0 would have to reach both the first PCU and the one after the max
+        let mut builder = ProgramBuilder::default();
+        let mut valmap = Channels::default();
+        let (_, a_recv) = builder.unbounded::<Token<f32, u32>>();
+        let (_, b_recv) = builder.unbounded::<Token<f32, u32>>();
+        let (out_send, _) = builder.unbounded::<Token<f32, u32>>();
+        let stages = vec![
+            (ValStage::Chained(ChainedOp::Binary(ALUMulOp())), vec![0, 1]),
+            (ValStage::ScalarMax(0.5), vec![2]),
+            (ValStage::Chained(ChainedOp::Binary(ALUAddOp())), vec![3, 0]),
+        ];
+        let in_vals = vec![a_recv, b_recv];
+        assert!(build_val_chain(stages, in_vals, out_send, &mut valmap, &mut builder).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use std::any::Any;
+use std::collections::HashMap;
+
+use dam::context_tools::{Receiver, Sender};
+use dam::simulation::ProgramBuilder;
+use linkme::distributed_slice;
+
+use super::validate::{Boundary, Dir, StreamKind};
+use super::{Channels, CrdElement, ValueElement, ST};
+use crate::cli_common::SamOptions;
+use crate::config::manifest::DataDir;
+use crate::templates::primitive::{Repsiggen, Token};
+
+/// An operator built outside of this crate, called from a graph like a function: a `Func` op whose
+/// name matches no function body is looked up here. Its bindings map the op's ports (the inner IDs)
+/// to streams of the calling graph.
+///
+/// Downstream crates write one builder, generic over the value and coordinate types, and register it with
+/// ```ignore
+/// fn build_my_unit<VT: ValueElement, CT: CrdElement>(
+///     mut args: CustomOpArgs<'_, '_, VT, CT>,
+/// ) -> Result<(), String> { ... }
+///
+/// #[distributed_slice(comal::proto_driver::custom::CUSTOM_OPS)]
+/// static MY_UNIT: CustomOp = CustomOp {
+///     name: "my_unit",
+///     inputs: &[(StreamKind::Val, 0)],
+///     outputs: &[(StreamKind::Val, 1)],
+///     builders: comal::custom_builders!(build_my_unit),
+/// };
+/// ```
+/// Ops that only support some types list those instantiations instead, e.g.
+/// `builders: &[&(build_my_unit::<f32, u32> as CustomBuildFn<f32, u32>)]`.
+pub struct CustomOp {
+    pub name: &'static str,
+    pub inputs: &'static [(StreamKind, u64)],
+    pub outputs: &'static [(StreamKind, u64)],
+
+    /// A [`CustomBuildFn`] per value/coordinate type pair the op supports
+    pub builders: &'static [&'static (dyn Any + Send + Sync)],
+}
+
+impl CustomOp {
+    pub(super) fn boundary(&self) -> Boundary {
+        let inputs = self.inputs.iter().map(|port| (*port, Dir::In));
+        let outputs = self.outputs.iter().map(|port| (*port, Dir::Out));
+        inputs.chain(outputs).collect()
+    }
+
+    /// The op's builder for the types the graph runs with, if it has one.
+    pub(super) fn builder<VT: ValueElement, CT: CrdElement>(
+        &self,
+    ) -> Option<CustomBuildFn<VT, CT>> {
+        (self.builders.iter())
+            .find_map(|build| build.downcast_ref::<CustomBuildFn<VT, CT>>().copied())
+    }
+}
+
+/// Everything a custom op's builder can reach. Ports are the inner IDs of the op's bindings.
+pub struct CustomOpArgs<'a, 'b, VT: ValueElement, CT: CrdElement> {
+    pub name: &'b str,
+    pub data: &'b DataDir,
+    pub sam_options: &'b SamOptions,
+    pub builder: &'b mut ProgramBuilder<'a>,
+    pub(super) ref_bindings: &'b HashMap<u64, u64>,
+    pub(super) crd_bindings: &'b HashMap<u64, u64>,
+    pub(super) val_bindings: &'b HashMap<u64, u64>,
+    pub(super) repsig_bindings: &'b HashMap<u64, u64>,
+    pub(super) refmap: &'b mut Channels<'a, Token<CT, ST>>,
+    pub(super) crdmap: &'b mut Channels<'a, Token<CT, ST>>,
+    pub(super) valmap: &'b mut Channels<'a, Token<VT, ST>>,
+    pub(super) repmap: &'b mut Channels<'a, Repsiggen>,
+}
+
+pub type CustomBuildFn<VT, CT> = for<'a, 'b> fn(CustomOpArgs<'a, 'b, VT, CT>) -> Result<(), String>;
+
+macro_rules! custom_op_ports {
+    ($(($kind: literal, $bindings: ident, $map: ident, $elem: ty, $receiver: ident, $sender: ident)),*) => {
+        impl<'a, 'b, VT: ValueElement, CT: CrdElement> CustomOpArgs<'a, 'b, VT, CT> {
+            $(
+                pub fn $receiver(&mut self, port: u64) -> Result<Receiver<$elem>, String> {
+                    let id = self.$bindings.get(&port).ok_or_else(|| {
+                        format!("custom op {:?} reads unbound {} port {port}", self.name, $kind)
+                    })?;
+                    Ok(self.$map.get_receiver(*id, self.builder))
+                }
+
+                /// Unbound output ports are voided, like unset outputs of the built-in ops.
+                pub fn $sender(&mut self, port: u64) -> Sender<$elem> {
+                    let id = self.$bindings.get(&port).copied().unwrap_or(0);
+                    self.$map.get_sender(id, self.builder)
+                }
+            )*
+        }
+    };
+}
+
+custom_op_ports!(
+    ("ref", ref_bindings, refmap, Token<CT, ST>, ref_receiver, ref_sender),
+    ("crd", crd_bindings, crdmap, Token<CT, ST>, crd_receiver, crd_sender),
+    ("val", val_bindings, valmap, Token<VT, ST>, val_receiver, val_sender),
+    ("repsig", repsig_bindings, repmap, Repsiggen, repsig_receiver, repsig_sender)
+);
+
+/// Instantiates a generic custom op builder for every value/coordinate type pair
+/// [`parse_proto`](super::parse_proto) can run a graph with.
+#[macro_export]
+macro_rules! custom_builders {
+    ($build: ident) => {
+        $crate::custom_builders!(
+            $build,
+            (f32, u32),
+            (f32, u64),
+            (f64, u32),
+            (f64, u64),
+            (i32, u32),
+            (i32, u64),
+            (i64, u32),
+            (i64, u64)
+        )
+    };
+    ($build: ident, $(($vt: ty, $ct: ty)),*) => {
+        &[$(&($build::<$vt, $ct> as $crate::proto_driver::custom::CustomBuildFn<$vt, $ct>)),*]
+    };
+}
+
+#[distributed_slice]
+pub static CUSTOM_OPS: [CustomOp] = [..];
+
+pub fn find_custom_op(name: &str) -> Option<&'static CustomOp> {
+    CUSTOM_OPS.iter().find(|op| op.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use linkme::distributed_slice;
+
+    use super::{CustomOp, CUSTOM_OPS};
+    use crate::proto_driver::graph_file::{decode_graph, GraphFormat};
+    use crate::proto_driver::validate::{validate, GraphError, StreamKind};
+
+    #[distributed_slice(CUSTOM_OPS)]
+    static PASSTHROUGH: CustomOp = CustomOp {
+        name: "test_passthrough",
+        inputs: &[(StreamKind::Val, 0)],
+        outputs: &[(StreamKind::Val, 1)],
+        builders: &[],
+    };
+
+    fn graph_calling(name: &str) -> String {
+        format!(
+            r#"{{
+            "graph": {{
+                "name": "main",
+                "operators": [
+                    {{"op": {{"Root": {{"output_ref": {{"id": {{"id": 1}}}}}}}}}},
+                    {{"op": {{"Array": {{
+                        "input_ref": {{"id": {{"id": 1}}}},
+                        "output_val": {{"id": {{"id": 2}}}},
+                        "tensor": "B"
+                    }}}}}},
+                    {{"op": {{"Func": {{"name": "{name}", "val_bindings": {{"0": 2, "1": 3}}}}}}}},
+                    {{"op": {{"ValWrite": {{"input_val": {{"id": {{"id": 3}}}}, "tensor": "X"}}}}}}
+                ]
+            }}
+        }}"#
+        )
+    }
+
+    #[test]
+    fn custom_op_validate_test() {
+        let graph = decode_graph(
+            graph_calling("test_passthrough").as_bytes(),
+            GraphFormat::Json,
+        );
+        assert_eq!(validate(&graph.unwrap(), |_, _| false, true), Ok(()));
+
+        let graph = decode_graph(
+            graph_calling("not_registered").as_bytes(),
+            GraphFormat::Json,
+        );
+        assert!(matches!(
+            validate(&graph.unwrap(), |_, _| false, true),
+            Err(GraphError::UnknownFunction { .. })
+        ));
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use itertools::Itertools;
+
+use super::proto_headers::tortilla::operation::*;
+use super::proto_headers::tortilla::*;
+use super::validate::{
+    function_boundaries, op_kind, op_streams, Boundary, Dir, GraphError, OpRef, StreamKind,
+};
+
+fn kind_color(kind: StreamKind) -> &'static str {
+    match kind {
+        StreamKind::Crd => "blue",
+        StreamKind::Ref => "red",
+        StreamKind::Val => "darkgreen",
+        StreamKind::Repsig => "orange",
+    }
+}
+
+fn op_label(op: &Op) -> String {
+    let detail = match op {
+        Op::FiberLookup(op) => format!("tensor {} mode {} ({})", op.tensor, op.mode, op.format),
+        Op::FiberWrite(op) => format!("tensor {} mode {}", op.tensor, op.mode),
+        Op::Array(op) => format!("tensor {}", op.tensor),
+        Op::ValWrite(op) => format!("tensor {}", op.tensor),
+        Op::Joiner(op) => format!("{:?}", op.join_type()),
+        Op::Alu(op) => (op.stages.iter())
+            .map(|stage| format!("{:?}", stage.op()))
+            .join(" -> "),
+        Op::CoordMask(op) => format!("{} {:?}", op.predicate, op.params),
+        Op::Func(op) => op.name.clone(),
+        _ => String::new(),
+    };
+    if detail.is_empty() {
+        op_kind(op).to_string()
+    } else {
+        format!("{}\n{}", op_kind(op), detail)
+    }
+}
+
+#[derive(Default)]
+struct StreamEnds {
+    kind: Option<StreamKind>,
+    producer: Option<String>,
+    consumer: Option<String>,
+}
+
+fn write_graph(
+    dot: &mut String,
+    graph: &ProgramGraph,
+    func: Option<&str>,
+    boundaries: &HashMap<String, Boundary>,
+    indent: &str,
+) -> Result<(), GraphError> {
+    let prefix = func.unwrap_or("main");
+    let mut streams: BTreeMap<u64, StreamEnds> = BTreeMap::new();
+    for (index, operation) in graph.operators.iter().enumerate() {
+        let opref = OpRef::new(func, index, operation.op.as_ref());
+        let op = operation
+            .op
+            .as_ref()
+            .ok_or_else(|| GraphError::MissingField {
+                op: opref.clone(),
+                field: "op",
+            })?;
+        let node = format!("{prefix}_op{index}");
+        writeln!(dot, "{indent}{node:?} [label={:?}];", op_label(op)).unwrap();
+        for stream in op_streams(op, &opref, boundaries)? {
+            let ends = streams.entry(stream.id).or_default();
+            ends.kind = Some(stream.kind);
+            match stream.dir {
+                Dir::In => ends.consumer = Some(node.clone()),
+                Dir::Out => ends.producer = Some(node.clone()),
+            }
+        }
+    }
+
+    // Streams that cross the graph boundary hang off a point node
+    for (id, ends) in streams {
+        let kind = ends.kind.unwrap();
+        let mut end = |node: Option<String>| {
+            node.unwrap_or_else(|| {
+                let point = format!("{prefix}_stream{id}");
+                writeln!(dot, "{indent}{point:?} [shape=point];").unwrap();
+                point
+            })
+        };
+        let producer = end(ends.producer);
+        let consumer = end(ends.consumer);
+        let color = kind_color(kind);
+        writeln!(
+            dot,
+            "{indent}{producer:?} -> {consumer:?} [label=\"{kind} {id}\", color={color}, fontcolor={color}];"
+        )
+        .unwrap();
+    }
+    Ok(())
+}
+
+/// Renders the proto graph as DOT: one node per op labeled with its type and tensor/mode,
+/// one edge per stream labeled with its ID and colored by kind. Function bodies are drawn as clusters.
+pub fn to_dot(comal_graph: &ComalGraph) -> Result<String, GraphError> {
+    let boundaries = function_boundaries(comal_graph)?;
+    let graph = comal_graph.graph.as_ref().ok_or(GraphError::MissingGraph)?;
+
+    let mut dot = String::new();
+    writeln!(dot, "digraph {:?} {{", graph.name).unwrap();
+    writeln!(dot, "  node [shape=box];").unwrap();
+    write_graph(&mut dot, graph, None, &boundaries, "  ")?;
+    for func in &comal_graph.funcs {
+        writeln!(dot, "  subgraph {:?} {{", format!("cluster_{}", func.name)).unwrap();
+        writeln!(dot, "    label={:?};", format!("func {}", func.name)).unwrap();
+        write_graph(&mut dot, func, Some(&func.name), &boundaries, "    ")?;
+        writeln!(dot, "  }}").unwrap();
+    }
+    writeln!(dot, "}}").unwrap();
+    Ok(dot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_dot;
+    use crate::proto_driver::graph_file::{decode_graph, GraphFormat};
+    use crate::proto_driver::proto_headers::tortilla::ComalGraph;
+    use crate::proto_driver::validate::GraphError;
+
+    #[test]
+    fn to_dot_test() {
+        let graph = r#"{
+            "graph": {
+                "name": "main",
+                "operators": [
+                    {"op": {"Root": {"output_ref": {"id": {"id": 1}}}}},
+                    {"op": {"Func": {"name": "copy", "ref_bindings": {"1": 1}, "val_bindings": {"2": 2}}}},
+                    {"op": {"ValWrite": {"input_val": {"id": {"id": 2}}, "tensor": "X"}}}
+                ]
+            },
+            "funcs": [{
+                "name": "copy",
+                "operators": [
+                    {"op": {"Array": {
+                        "input_ref": {"id": {"id": 1}},
+                        "output_val": {"id": {"id": 2}},
+                        "tensor": "B"
+                    }}}
+                ]
+            }]
+        }"#;
+        let graph = decode_graph(graph.as_bytes(), GraphFormat::Json).unwrap();
+        let dot = to_dot(&graph).unwrap();
+        let lines: Vec<&str> = dot.lines().map(str::trim).collect();
+        for expected in [
+            r#"digraph "main" {"#,
+            r#""main_op0" [label="Root"];"#,
+            r#""main_op2" [label="ValWrite\ntensor X"];"#,
+            r#""main_op0" -> "main_op1" [label="ref 1", color=red, fontcolor=red];"#,
+            r#""main_op1" -> "main_op2" [label="val 2", color=darkgreen, fontcolor=darkgreen];"#,
+            r#"subgraph "cluster_copy" {"#,
+            r#""copy_op0" [label="Array\ntensor B"];"#,
+            // The body's ports hang off point nodes
+            r#""copy_stream1" -> "copy_op0" [label="ref 1", color=red, fontcolor=red];"#,
+            r#""copy_op0" -> "copy_stream2" [label="val 2", color=darkgreen, fontcolor=darkgreen];"#,
+        ] {
+            assert!(lines.contains(&expected), "{expected} missing from\n{dot}");
+        }
+    }
+
+    #[test]
+    fn to_dot_error_test() {
+        assert!(matches!(
+            to_dot(&ComalGraph::default()),
+            Err(GraphError::MissingGraph)
+        ));
+    }
+}
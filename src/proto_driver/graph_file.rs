@@ -0,0 +1,93 @@
+use std::fs;
+use std::path::Path;
+
+use prost::Message;
+
+use super::proto_headers::tortilla::ComalGraph;
+
+/// On-disk encodings of a ComalGraph, picked by file extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Protobuf wire format, as emitted by the compiler
+    Binary,
+    /// serde mapping of the generated prost types, meant for hand-written and hand-edited graphs
+    Json,
+}
+
+impl GraphFormat {
+    /// `.json` files are JSON, everything else (`.bin`, `.pb`, ...) is binary protobuf.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => GraphFormat::Json,
+            _ => GraphFormat::Binary,
+        }
+    }
+}
+
+pub fn decode_graph(contents: &[u8], format: GraphFormat) -> anyhow::Result<ComalGraph> {
+    Ok(match format {
+        GraphFormat::Binary => ComalGraph::decode(contents)?,
+        GraphFormat::Json => serde_json::from_slice(contents)?,
+    })
+}
+
+pub fn encode_graph(graph: &ComalGraph, format: GraphFormat) -> anyhow::Result<Vec<u8>> {
+    Ok(match format {
+        GraphFormat::Binary => graph.encode_to_vec(),
+        GraphFormat::Json => serde_json::to_vec_pretty(graph)?,
+    })
+}
+
+pub fn read_graph(path: &Path) -> anyhow::Result<ComalGraph> {
+    let contents = fs::read(path)?;
+    decode_graph(&contents, GraphFormat::from_path(path))
+}
+
+pub fn write_graph(graph: &ComalGraph, path: &Path) -> anyhow::Result<()> {
+    fs::write(path, encode_graph(graph, GraphFormat::from_path(path))?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{decode_graph, encode_graph, GraphFormat};
+    use crate::proto_driver::proto_headers::tortilla::{ComalGraph, ProgramGraph};
+
+    #[test]
+    fn format_from_path_test() {
+        assert_eq!(
+            GraphFormat::from_path(Path::new("matmul.json")),
+            GraphFormat::Json
+        );
+        assert_eq!(
+            GraphFormat::from_path(Path::new("matmul.bin")),
+            GraphFormat::Binary
+        );
+        assert_eq!(
+            GraphFormat::from_path(Path::new("matmul")),
+            GraphFormat::Binary
+        );
+    }
+
+    #[test]
+    fn json_round_trip_test() {
+        let graph = ComalGraph {
+            graph: Some(ProgramGraph {
+                name: "main".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        for format in [GraphFormat::Binary, GraphFormat::Json] {
+            let encoded = encode_graph(&graph, format).unwrap();
+            assert_eq!(decode_graph(&encoded, format).unwrap(), graph);
+        }
+        // Missing fields take their proto defaults
+        assert_eq!(
+            decode_graph(b"{}", GraphFormat::Json).unwrap(),
+            ComalGraph::default()
+        );
+    }
+}
@@ -0,0 +1,451 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use num::ToPrimitive;
+
+use crate::config::manifest::{DataDir, TensorFile};
+use crate::templates::data_file::{data_exists, read_data, DataElement};
+use crate::templates::tensor::Tensor;
+use crate::templates::wr_scanner::BatchMarks;
+
+use super::ST;
+
+/// A write scanner's array, read back after the simulation whatever its element type.
+trait OutputArray {
+    /// Entries as written to disk, blocks flattened in row-major order
+    fn lines(&self) -> Vec<String>;
+
+    fn to_f64(&self) -> Vec<f64>;
+
+    fn to_u64(&self) -> Vec<u64>;
+
+    /// Number of scalars per entry
+    fn block_len(&self) -> usize;
+}
+
+struct Scalars<T>(Arc<Mutex<Vec<T>>>);
+
+impl<T: Display + ToPrimitive> OutputArray for Scalars<T> {
+    fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap().iter().map(T::to_string).collect()
+    }
+
+    fn to_f64(&self) -> Vec<f64> {
+        (self.0.lock().unwrap().iter())
+            .map(|val| val.to_f64().unwrap())
+            .collect()
+    }
+
+    fn to_u64(&self) -> Vec<u64> {
+        (self.0.lock().unwrap().iter())
+            .map(|val| val.to_u64().unwrap())
+            .collect()
+    }
+
+    fn block_len(&self) -> usize {
+        1
+    }
+}
+
+struct Blocks<T: dam::types::DAMType>(Arc<Mutex<Vec<Tensor<'static, T>>>>);
+
+impl<T: dam::types::DAMType + Display + ToPrimitive> Blocks<T> {
+    fn flattened<U>(&self, conv: impl Fn(&T) -> U) -> Vec<U> {
+        (self.0.lock().unwrap().iter())
+            .flat_map(|block| block.data.iter().flat_map(|data| data.iter()))
+            .map(conv)
+            .collect()
+    }
+}
+
+impl<T: dam::types::DAMType + Display + ToPrimitive> OutputArray for Blocks<T> {
+    fn lines(&self) -> Vec<String> {
+        self.flattened(T::to_string)
+    }
+
+    fn to_f64(&self) -> Vec<f64> {
+        self.flattened(|val| val.to_f64().unwrap())
+    }
+
+    fn to_u64(&self) -> Vec<u64> {
+        self.flattened(|val| val.to_u64().unwrap())
+    }
+
+    fn block_len(&self) -> usize {
+        (self.0.lock().unwrap().iter())
+            .find_map(|block| block.data.as_ref().map(|data| data.len()))
+            .unwrap_or(1)
+    }
+}
+
+struct FiberOutput {
+    tensor: String,
+    mode: u64,
+    seg: Box<dyn OutputArray>,
+    crd: Box<dyn OutputArray>,
+}
+
+/// Handles to the arrays filled in by the graph's write scanners, so results outlive the simulation.
+#[derive(Default)]
+pub struct TensorOutputs {
+    fibers: Vec<FiberOutput>,
+    vals: Vec<(String, Box<dyn OutputArray>)>,
+    marks: Vec<Arc<Mutex<BatchMarks<ST>>>>,
+}
+
+impl TensorOutputs {
+    pub fn add_fiber<CT: Display + ToPrimitive + 'static>(
+        &mut self,
+        tensor: String,
+        mode: u64,
+        seg: Arc<Mutex<Vec<CT>>>,
+        crd: Arc<Mutex<Vec<CT>>>,
+    ) {
+        self.fibers.push(FiberOutput {
+            tensor,
+            mode,
+            seg: Box::new(Scalars(seg)),
+            crd: Box::new(Scalars(crd)),
+        });
+    }
+
+    pub fn add_vals<VT: Display + ToPrimitive + 'static>(
+        &mut self,
+        tensor: String,
+        vals: Arc<Mutex<Vec<VT>>>,
+    ) {
+        self.vals.push((tensor, Box::new(Scalars(vals))));
+    }
+
+    pub fn add_block_vals<VT: dam::types::DAMType + Display + ToPrimitive + 'static>(
+        &mut self,
+        tensor: String,
+        vals: Arc<Mutex<Vec<Tensor<'static, VT>>>>,
+    ) {
+        self.vals.push((tensor, Box::new(Blocks(vals))));
+    }
+
+    pub fn add_marks(&mut self, marks: Arc<Mutex<BatchMarks<ST>>>) {
+        self.marks.push(marks);
+    }
+
+    /// Cycles spent on each batch: a batch ends once every writer has finished it,
+    /// and the next one is counted from there.
+    pub fn batch_cycles(&self, num_batches: usize) -> Vec<u64> {
+        let mut ends = vec![0; num_batches];
+        for marks in &self.marks {
+            let writer_ends = marks.lock().unwrap().batch_ends(num_batches);
+            ends.iter_mut()
+                .zip(writer_ends)
+                .for_each(|(end, writer_end)| *end = (*end).max(writer_end));
+        }
+        let mut prev = 0;
+        ends.into_iter()
+            .map(|end| {
+                let cycles = end.saturating_sub(prev);
+                prev = prev.max(end);
+                cycles
+            })
+            .collect()
+    }
+
+    /// Dumps every written tensor into `dir` with one value per line, the same layout `read_data` consumes.
+    /// Blocked values are flattened block by block in row-major order.
+    /// The graph doesn't know the output sizes, so each mode's shape is one past its largest coordinate.
+    pub fn write_to_dir(&self, dir: &Path) -> std::io::Result<()> {
+        fs::create_dir_all(dir)?;
+        for fiber in &self.fibers {
+            let prefix = format!("tensor_{}_mode_{}", fiber.tensor, fiber.mode);
+            write_lines(&dir.join(format!("{prefix}_seg")), fiber.seg.lines())?;
+            write_lines(&dir.join(format!("{prefix}_crd")), fiber.crd.lines())?;
+        }
+        let tensors: BTreeSet<&str> = self
+            .fibers
+            .iter()
+            .map(|fiber| fiber.tensor.as_str())
+            .collect();
+        for tensor in tensors {
+            let mut fibers: Vec<&FiberOutput> = (self.fibers.iter())
+                .filter(|fiber| fiber.tensor == tensor)
+                .collect();
+            fibers.sort_by_key(|fiber| fiber.mode);
+            let shape = fibers.iter().map(|fiber| {
+                fiber
+                    .crd
+                    .to_u64()
+                    .into_iter()
+                    .max()
+                    .map_or(0, |crd| crd + 1)
+            });
+            write_lines(&dir.join(format!("tensor_{tensor}_mode_shape")), shape)?;
+        }
+        for (tensor, vals) in &self.vals {
+            write_lines(
+                &dir.join(format!("tensor_{tensor}_mode_vals")),
+                vals.lines(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The first difference between a written tensor and its reference files.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mismatch {
+    MissingReference(PathBuf),
+    BadReference {
+        path: PathBuf,
+        reason: String,
+    },
+    Level {
+        tensor: String,
+        mode: u64,
+        array: &'static str,
+        index: usize,
+        expected: Option<u64>,
+        actual: Option<u64>,
+    },
+    Value {
+        tensor: String,
+        coords: Vec<u64>,
+        position: usize,
+        expected: Option<f64>,
+        actual: Option<f64>,
+    },
+}
+
+fn fmt_entry<T: Display>(entry: &Option<T>) -> String {
+    match entry {
+        Some(val) => val.to_string(),
+        None => "nothing".to_string(),
+    }
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mismatch::MissingReference(path) => write!(f, "missing reference file {path:?}"),
+            Mismatch::BadReference { path, reason } => {
+                write!(f, "can't read reference file {path:?}: {reason}")
+            }
+            Mismatch::Level {
+                tensor,
+                mode,
+                array,
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "tensor {tensor} mode {mode} {array}: first mismatch at index {index}, expected {}, got {}",
+                fmt_entry(expected),
+                fmt_entry(actual)
+            ),
+            Mismatch::Value {
+                tensor,
+                coords,
+                position,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "tensor {tensor} vals: first mismatch at coordinate {coords:?} (position {position}), expected {}, got {}",
+                fmt_entry(expected),
+                fmt_entry(actual)
+            ),
+        }
+    }
+}
+
+impl TensorOutputs {
+    /// Compares every written tensor against its reference files in `data`,
+    /// returning the first mismatch of each tensor that differs.
+    pub fn check_against_dir(&self, data: &DataDir, tolerance: f64) -> Vec<Mismatch> {
+        let tensors: BTreeSet<&str> = (self.fibers.iter().map(|fiber| fiber.tensor.as_str()))
+            .chain(self.vals.iter().map(|(tensor, _)| tensor.as_str()))
+            .collect();
+        tensors
+            .into_iter()
+            .filter_map(|tensor| self.check_tensor(data, tensor, tolerance).err())
+            .collect()
+    }
+
+    fn check_tensor(&self, data: &DataDir, tensor: &str, tolerance: f64) -> Result<(), Mismatch> {
+        let mut fibers: Vec<&FiberOutput> = (self.fibers.iter())
+            .filter(|fiber| fiber.tensor == tensor)
+            .collect();
+        fibers.sort_by_key(|fiber| fiber.mode);
+
+        let mut levels = vec![];
+        for fiber in fibers {
+            let expected_seg = read_reference(&data.path(tensor, TensorFile::Seg(fiber.mode)))?;
+            let expected_crd = read_reference(&data.path(tensor, TensorFile::Crd(fiber.mode)))?;
+            for (array, expected, actual) in [
+                ("seg", &expected_seg, &fiber.seg),
+                ("crd", &expected_crd, &fiber.crd),
+            ] {
+                let actual = actual.to_u64();
+                if let Some(index) = first_diff(expected, &actual, |e, a| e == a) {
+                    return Err(Mismatch::Level {
+                        tensor: tensor.to_string(),
+                        mode: fiber.mode,
+                        array,
+                        index,
+                        expected: expected.get(index).copied(),
+                        actual: actual.get(index).copied(),
+                    });
+                }
+            }
+            levels.push((expected_seg, expected_crd));
+        }
+
+        let Some((_, vals)) = self.vals.iter().find(|(name, _)| name == tensor) else {
+            return Ok(());
+        };
+        let (actual, block_len) = (vals.to_f64(), vals.block_len());
+        let expected: Vec<f64> = read_reference(&data.path(tensor, TensorFile::Vals))?;
+        match first_diff(&expected, &actual, |e, a| (e - a).abs() <= tolerance) {
+            Some(position) => {
+                let mut coords = coords_of(&levels, position / block_len);
+                if block_len > 1 {
+                    coords.push((position % block_len) as u64);
+                }
+                Err(Mismatch::Value {
+                    tensor: tensor.to_string(),
+                    coords,
+                    position,
+                    expected: expected.get(position).copied(),
+                    actual: actual.get(position).copied(),
+                })
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+fn read_reference<T: DataElement>(path: &Path) -> Result<Vec<T>, Mismatch> {
+    if !data_exists(path) {
+        return Err(Mismatch::MissingReference(path.to_path_buf()));
+    }
+    read_data(path).map_err(|err| Mismatch::BadReference {
+        path: path.to_path_buf(),
+        reason: err.to_string(),
+    })
+}
+
+fn first_diff<T>(expected: &[T], actual: &[T], matches: impl Fn(&T, &T) -> bool) -> Option<usize> {
+    (0..expected.len().max(actual.len())).find(|&idx| match (expected.get(idx), actual.get(idx)) {
+        (Some(e), Some(a)) => !matches(e, a),
+        _ => true,
+    })
+}
+
+/// Walks the reference levels from the innermost out to recover the coordinate of a value position.
+fn coords_of(levels: &[(Vec<u64>, Vec<u64>)], position: usize) -> Vec<u64> {
+    let mut coords = vec![];
+    let mut pos = position;
+    for (seg, crd) in levels.iter().rev() {
+        match crd.get(pos) {
+            Some(crd) => coords.push(*crd),
+            None => break,
+        }
+        pos = seg.partition_point(|&start| start as usize <= pos) - 1;
+    }
+    coords.reverse();
+    coords
+}
+
+fn write_lines<T: Display>(path: &Path, vals: impl IntoIterator<Item = T>) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for val in vals {
+        writeln!(writer, "{val}")?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{coords_of, first_diff, read_reference, Mismatch, TensorOutputs};
+    use crate::config::manifest::{DataDir, TensorFile};
+    use crate::templates::data_file::read_data;
+    use crate::utils::scratch::ScratchDir;
+
+    #[test]
+    fn coords_of_test() {
+        // [[1, 0, 2], [0, 0, 0], [0, 3, 0]] with the empty row kept as an empty fiber
+        let levels = vec![
+            (vec![0, 3], vec![0, 1, 2]),
+            (vec![0, 2, 2, 3], vec![0, 2, 1]),
+        ];
+        assert_eq!(coords_of(&levels, 0), vec![0, 0]);
+        assert_eq!(coords_of(&levels, 1), vec![0, 2]);
+        assert_eq!(coords_of(&levels, 2), vec![2, 1]);
+        assert_eq!(coords_of(&levels, 3), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn first_diff_test() {
+        let close = |e: &f32, a: &f32| (e - a).abs() <= 1e-3;
+        assert_eq!(first_diff(&[1.0, 2.0], &[1.0, 2.0005], close), None);
+        assert_eq!(first_diff(&[1.0, 2.0], &[1.0, 2.5], close), Some(1));
+        assert_eq!(first_diff(&[1.0, 2.0], &[1.0], close), Some(1));
+    }
+
+    #[test]
+    fn read_reference_test() {
+        let dir = ScratchDir::new("read_reference");
+        let path = dir.write("good", "1\n2\n");
+        assert_eq!(read_reference::<u64>(&path), Ok(vec![1, 2]));
+
+        let path = dir.write("bad", "1\nx\n");
+        assert!(matches!(
+            read_reference::<u64>(&path),
+            Err(Mismatch::BadReference { reason, .. }) if reason.contains("line 2")
+        ));
+        assert_eq!(
+            read_reference::<u64>(&dir.path().join("missing")),
+            Err(Mismatch::MissingReference(dir.path().join("missing")))
+        );
+    }
+
+    #[test]
+    fn write_round_trip_test() {
+        // [[0, 1, 0, 0], [0, 0, 0, 0], [2, 0, 0, 3]]
+        fn shared<T>(vals: Vec<T>) -> Arc<Mutex<Vec<T>>> {
+            Arc::new(Mutex::new(vals))
+        }
+        let mut outputs = TensorOutputs::default();
+        outputs.add_fiber(
+            "X".to_string(),
+            0,
+            shared(vec![0u32, 2]),
+            shared(vec![0, 2]),
+        );
+        outputs.add_fiber(
+            "X".to_string(),
+            1,
+            shared(vec![0, 1, 3]),
+            shared(vec![1, 0, 3]),
+        );
+        outputs.add_vals("X".to_string(), shared(vec![1f32, 2.0, 3.0]));
+
+        let dir = ScratchDir::new("write_round_trip");
+        outputs.write_to_dir(dir.path()).unwrap();
+        let data = DataDir::open(dir.path()).unwrap();
+        let read = |file| read_data::<u64>(&data.path("X", file)).unwrap();
+        assert_eq!(read(TensorFile::Shape), vec![3, 4]);
+        assert_eq!(read(TensorFile::Seg(1)), vec![0, 1, 3]);
+        assert_eq!(read(TensorFile::Crd(1)), vec![1, 0, 3]);
+        assert_eq!(
+            read_data::<f32>(&data.path("X", TensorFile::Vals)).unwrap(),
+            vec![1.0, 2.0, 3.0]
+        );
+        assert_eq!(outputs.check_against_dir(&data, 0.0), vec![]);
+    }
+}
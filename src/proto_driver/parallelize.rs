@@ -0,0 +1,635 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use super::find_block_streams;
+use super::proto_headers::tortilla::operation::*;
+use super::proto_headers::tortilla::*;
+use super::util::{get_crd_id, get_ref_id, get_repsig_id, get_val_id, AsStreamID};
+use super::validate::{function_boundaries, op_streams, Dir, GraphError, OpRef, StreamKind};
+
+/// A stream of the original graph and the per-lane streams that replace it in the parallel copies.
+/// `depth` is how many fiber levels a gathered stream is below `split`, and 0 for scattered ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LaneSplit {
+    pub kind: StreamKind,
+    pub id: u64,
+    pub lanes: Vec<u64>,
+    pub depth: usize,
+}
+
+/// Contexts the transformed graph expects around its lanes: a Scatter from each `scatters` stream
+/// into its lanes, and an ordered Gather from the lanes of each `gathers` stream back into it, which
+/// follows a copy of the `split` stream to put the lanes' fibers back in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParallelPlan {
+    pub split: u64,
+    pub scatters: Vec<LaneSplit>,
+    pub gathers: Vec<LaneSplit>,
+}
+
+/// How an output of an op lines up with one of its inputs: the output is `offset` fiber levels below
+/// the input, and `drops` is set when the op can add or remove elements of the input's own level.
+struct LevelLink {
+    output: u64,
+    input: u64,
+    offset: i64,
+    drops: bool,
+}
+
+impl LevelLink {
+    fn new(output: u64, input: u64, offset: i64) -> Self {
+        Self {
+            output,
+            input,
+            offset,
+            drops: false,
+        }
+    }
+
+    fn dropping(output: u64, input: u64, offset: i64) -> Self {
+        Self {
+            output,
+            input,
+            offset,
+            drops: true,
+        }
+    }
+}
+
+/// The fiber level of each output of `op` relative to its inputs, or None for ops whose outputs
+/// don't follow the fibers of any one input.
+fn level_links(op: &Op) -> Option<Vec<LevelLink>> {
+    let links = match op {
+        Op::Broadcast(op) => {
+            let (input, outputs): (u64, Vec<u64>) = match op.conn.as_ref()? {
+                broadcast::Conn::Crd(conn) => (
+                    get_crd_id(&conn.input),
+                    conn.outputs
+                        .iter()
+                        .map(|stream| stream.try_conv())
+                        .collect(),
+                ),
+                broadcast::Conn::Ref(conn) => (
+                    get_ref_id(&conn.input),
+                    conn.outputs
+                        .iter()
+                        .map(|stream| stream.try_conv())
+                        .collect(),
+                ),
+                broadcast::Conn::Val(conn) => (
+                    get_val_id(&conn.input),
+                    conn.outputs
+                        .iter()
+                        .map(|stream| stream.try_conv())
+                        .collect(),
+                ),
+                broadcast::Conn::Repsig(conn) => (
+                    get_repsig_id(&conn.input),
+                    conn.outputs
+                        .iter()
+                        .map(|stream| stream.try_conv())
+                        .collect(),
+                ),
+            };
+            (outputs.into_iter())
+                .map(|output| LevelLink::new(output, input, 0))
+                .collect()
+        }
+        Op::Joiner(op) => {
+            let first = op.input_pairs.first()?;
+            let mut links = vec![LevelLink::dropping(
+                get_crd_id(&op.output_crd),
+                get_crd_id(&first.crd),
+                0,
+            )];
+            for (pair, output) in op.input_pairs.iter().zip(&op.output_refs) {
+                links.push(LevelLink::dropping(
+                    output.try_conv(),
+                    get_ref_id(&pair.r#ref),
+                    0,
+                ));
+            }
+            links
+        }
+        Op::FiberLookup(op) => {
+            let input = get_ref_id(&op.input_ref);
+            vec![
+                LevelLink::new(get_crd_id(&op.output_crd), input, 1),
+                LevelLink::new(get_ref_id(&op.output_ref), input, 1),
+            ]
+        }
+        Op::Repeat(op) => {
+            let input = match get_crd_id(&op.input_rep_crd) {
+                0 => get_ref_id(&op.input_rep_ref),
+                id => id,
+            };
+            vec![LevelLink::new(get_ref_id(&op.output_ref), input, 0)]
+        }
+        Op::Repeatsig(op) => vec![LevelLink::new(
+            get_repsig_id(&op.output_rep_sig),
+            get_crd_id(&op.input_crd),
+            0,
+        )],
+        Op::Alu(op) => match op.conn.as_ref()? {
+            alu::Conn::Vals(conn) => vec![LevelLink::new(
+                get_val_id(&conn.output),
+                conn.inputs.first()?.try_conv(),
+                0,
+            )],
+            alu::Conn::Crds(conn) => vec![LevelLink::new(
+                get_crd_id(&conn.output),
+                conn.inputs.first()?.try_conv(),
+                0,
+            )],
+        },
+        Op::Reduce(op) => vec![LevelLink::new(
+            get_val_id(&op.output_val),
+            get_val_id(&op.input_val),
+            -1,
+        )],
+        // The held outer coordinate is repeated to the shape of the inner fibers
+        Op::CoordHold(op) => {
+            let inner = get_crd_id(&op.input_inner_crd);
+            vec![
+                LevelLink::new(get_crd_id(&op.output_inner_crd), inner, 0),
+                LevelLink::new(get_crd_id(&op.output_outer_crd), inner, 0),
+            ]
+        }
+        // Dropping an outer coordinate drops its whole inner fiber
+        Op::CoordDrop(op) => {
+            let outer = get_crd_id(&op.input_outer_crd);
+            vec![
+                LevelLink::dropping(get_crd_id(&op.output_outer_crd), outer, 0),
+                LevelLink::dropping(get_crd_id(&op.output_inner_crd), outer, 1),
+            ]
+        }
+        Op::CoordMask(op) => vec![
+            LevelLink::new(
+                get_crd_id(&op.output_outer_crd),
+                get_crd_id(&op.input_outer_crd),
+                0,
+            ),
+            LevelLink::dropping(
+                get_crd_id(&op.output_inner_crd),
+                get_crd_id(&op.input_inner_crd),
+                0,
+            ),
+            LevelLink::dropping(get_ref_id(&op.output_ref), get_ref_id(&op.input_ref), 0),
+        ],
+        Op::Array(op) => vec![LevelLink::new(
+            get_val_id(&op.output_val),
+            get_ref_id(&op.input_ref),
+            0,
+        )],
+        Op::Spacc(_) | Op::Func(_) => return None,
+        Op::Root(_) | Op::FiberWrite(_) | Op::ValWrite(_) => vec![],
+    };
+    Some(links)
+}
+
+/// The fiber level of each stream below the Root, for the streams whose level follows from it.
+/// Root batches are separated by stops at the root level, so in a stream at level `l` they are `Stop(l)`.
+pub(super) fn root_levels(graph: &ProgramGraph) -> HashMap<u64, i64> {
+    let links: Vec<LevelLink> = (graph.operators.iter())
+        .filter_map(|operation| operation.op.as_ref().and_then(level_links))
+        .flatten()
+        .collect();
+    let mut levels: HashMap<u64, i64> = (graph.operators.iter())
+        .filter_map(|operation| match operation.op.as_ref() {
+            Some(Op::Root(op)) => Some((get_ref_id(&op.output_ref), 0)),
+            _ => None,
+        })
+        .collect();
+    loop {
+        let num_found = levels.len();
+        for link in &links {
+            if let Some(&level) = levels.get(&link.input) {
+                levels.entry(link.output).or_insert(level + link.offset);
+            }
+        }
+        if levels.len() == num_found {
+            return levels;
+        }
+    }
+}
+
+trait RemapStreams {
+    fn remap(&mut self, lane: &HashMap<u64, u64>);
+}
+
+macro_rules! remap_streams_impl {
+    ($($stream: ty),*) => {
+        $(
+            impl RemapStreams for $stream {
+                fn remap(&mut self, lane: &HashMap<u64, u64>) {
+                    if let Some(id) = self.id.as_mut() {
+                        if let Some(new_id) = lane.get(&id.id) {
+                            id.id = *new_id;
+                        }
+                    }
+                }
+            }
+        )*
+    };
+}
+
+remap_streams_impl!(CrdStream, RefStream, ValStream, RepSigStream);
+
+impl<T: RemapStreams> RemapStreams for Option<T> {
+    fn remap(&mut self, lane: &HashMap<u64, u64>) {
+        if let Some(stream) = self {
+            stream.remap(lane);
+        }
+    }
+}
+
+impl<T: RemapStreams> RemapStreams for Vec<T> {
+    fn remap(&mut self, lane: &HashMap<u64, u64>) {
+        self.iter_mut().for_each(|stream| stream.remap(lane));
+    }
+}
+
+/// Renames every stream of `op` that has an entry in `lane`.
+fn remap_op(op: &mut Op, lane: &HashMap<u64, u64>) {
+    match op {
+        Op::Broadcast(op) => match op.conn.as_mut() {
+            Some(broadcast::Conn::Crd(conn)) => {
+                conn.input.remap(lane);
+                conn.outputs.remap(lane);
+            }
+            Some(broadcast::Conn::Ref(conn)) => {
+                conn.input.remap(lane);
+                conn.outputs.remap(lane);
+            }
+            Some(broadcast::Conn::Val(conn)) => {
+                conn.input.remap(lane);
+                conn.outputs.remap(lane);
+            }
+            Some(broadcast::Conn::Repsig(conn)) => {
+                conn.input.remap(lane);
+                conn.outputs.remap(lane);
+            }
+            None => (),
+        },
+        Op::Joiner(op) => {
+            for pair in &mut op.input_pairs {
+                pair.crd.remap(lane);
+                pair.r#ref.remap(lane);
+            }
+            op.output_refs.remap(lane);
+            op.output_crd.remap(lane);
+        }
+        Op::FiberLookup(op) => {
+            op.input_ref.remap(lane);
+            op.output_crd.remap(lane);
+            op.output_ref.remap(lane);
+        }
+        Op::FiberWrite(op) => op.input_crd.remap(lane),
+        Op::Repeat(op) => {
+            op.input_ref.remap(lane);
+            op.input_rep_crd.remap(lane);
+            op.input_rep_ref.remap(lane);
+            op.output_ref.remap(lane);
+        }
+        Op::Repeatsig(op) => {
+            op.input_crd.remap(lane);
+            op.output_rep_sig.remap(lane);
+        }
+        Op::Alu(op) => match op.conn.as_mut() {
+            Some(alu::Conn::Vals(conn)) => {
+                conn.inputs.remap(lane);
+                conn.output.remap(lane);
+            }
+            Some(alu::Conn::Crds(conn)) => {
+                conn.inputs.remap(lane);
+                conn.output.remap(lane);
+            }
+            None => (),
+        },
+        Op::Reduce(op) => {
+            op.input_val.remap(lane);
+            op.output_val.remap(lane);
+        }
+        Op::CoordHold(op) => {
+            op.input_inner_crd.remap(lane);
+            op.input_outer_crd.remap(lane);
+            op.output_inner_crd.remap(lane);
+            op.output_outer_crd.remap(lane);
+        }
+        Op::CoordDrop(op) => {
+            op.input_inner_crd.remap(lane);
+            op.input_outer_crd.remap(lane);
+            op.output_inner_crd.remap(lane);
+            op.output_outer_crd.remap(lane);
+        }
+        Op::Array(op) => {
+            op.input_ref.remap(lane);
+            op.output_val.remap(lane);
+        }
+        Op::Spacc(op) => {
+            op.input_inner_crd.remap(lane);
+            op.input_outer_crds.remap(lane);
+            op.input_val.remap(lane);
+            op.output_inner_crd.remap(lane);
+            op.output_outer_crds.remap(lane);
+            op.output_val.remap(lane);
+        }
+        Op::ValWrite(op) => op.input_val.remap(lane),
+        Op::CoordMask(op) => {
+            op.input_inner_crd.remap(lane);
+            op.input_outer_crd.remap(lane);
+            op.input_ref.remap(lane);
+            op.output_inner_crd.remap(lane);
+            op.output_outer_crd.remap(lane);
+            op.output_ref.remap(lane);
+        }
+        // Only the caller's side of the bindings lives in this graph
+        Op::Func(op) => {
+            for bindings in [
+                &mut op.ref_bindings,
+                &mut op.crd_bindings,
+                &mut op.val_bindings,
+                &mut op.repsig_bindings,
+            ] {
+                for outer in bindings.values_mut() {
+                    if let Some(new_id) = lane.get(outer) {
+                        *outer = *new_id;
+                    }
+                }
+            }
+        }
+        Op::Root(op) => op.output_ref.remap(lane),
+    }
+}
+
+fn is_writer(op: &Op) -> bool {
+    matches!(op, Op::FiberWrite(_) | Op::ValWrite(_))
+}
+
+/// Splits the top-level graph into `factor` parallel lanes at stream `split`.
+///
+/// Every op downstream of `split` is replicated once per lane with fresh stream IDs, stopping at the
+/// writers, which stay single. Streams entering the replicated region (`split` included) are
+/// scattered round-robin across the lanes and the streams leaving it are gathered back, as listed in
+/// the returned plan. Streams entering the region are assumed to be aligned token-for-token with
+/// `split`, like the co-iterated refs of a matmul's j loop. Gathered streams must be at or below the
+/// level of `split`, with one element or fiber per element of `split`.
+pub fn parallelize(
+    comal_graph: &ComalGraph,
+    split: u64,
+    factor: usize,
+) -> Result<(ComalGraph, ParallelPlan), GraphError> {
+    let bad_split = |reason: String| GraphError::BadSplit { id: split, reason };
+    if factor < 2 {
+        return Err(bad_split(format!(
+            "a parallel factor of {factor} leaves nothing to split"
+        )));
+    }
+    let boundaries = function_boundaries(comal_graph)?;
+    let graph = comal_graph.graph.as_ref().ok_or(GraphError::MissingGraph)?;
+
+    let mut uses = Vec::with_capacity(graph.operators.len());
+    for (index, operation) in graph.operators.iter().enumerate() {
+        let opref = OpRef::new(None, index, operation.op.as_ref());
+        let op = operation
+            .op
+            .as_ref()
+            .ok_or_else(|| GraphError::MissingField {
+                op: opref.clone(),
+                field: "op",
+            })?;
+        uses.push((op, op_streams(op, &opref, &boundaries)?));
+    }
+    let mut kinds = HashMap::new();
+    let mut consumers = HashMap::new();
+    for (index, (_, streams)) in uses.iter().enumerate() {
+        for stream in streams {
+            kinds.insert(stream.id, stream.kind);
+            if stream.dir == Dir::In {
+                consumers.insert(stream.id, index);
+            }
+        }
+    }
+
+    let mut region = BTreeSet::new();
+    let mut frontier = vec![split];
+    while let Some(id) = frontier.pop() {
+        let Some(&index) = consumers.get(&id) else {
+            continue;
+        };
+        let (op, streams) = &uses[index];
+        if is_writer(op) || !region.insert(index) {
+            continue;
+        }
+        frontier.extend(
+            (streams.iter())
+                .filter(|stream| stream.dir == Dir::Out)
+                .map(|stream| stream.id),
+        );
+    }
+    if region.is_empty() {
+        return Err(bad_split(
+            "nothing but a writer reads it, so there is nothing to replicate".to_string(),
+        ));
+    }
+
+    let mut inputs = BTreeSet::new();
+    let mut outputs = BTreeSet::new();
+    for &index in &region {
+        for stream in &uses[index].1 {
+            match stream.dir {
+                Dir::In => inputs.insert(stream.id),
+                Dir::Out => outputs.insert(stream.id),
+            };
+        }
+    }
+
+    // Fresh IDs start past everything the graph already uses
+    let mut next_id = (uses.iter())
+        .flat_map(|(_, streams)| streams.iter().map(|stream| stream.id))
+        .max()
+        .unwrap_or(0);
+    let lanes: Vec<HashMap<u64, u64>> = (0..factor)
+        .map(|_| {
+            (inputs.union(&outputs))
+                .map(|&id| {
+                    next_id += 1;
+                    (id, next_id)
+                })
+                .collect()
+        })
+        .collect();
+
+    let funcs = (comal_graph.funcs.iter())
+        .map(|func| (func.name.clone(), func.clone()))
+        .collect();
+    let block_vals = find_block_streams(graph, &funcs, &HashSet::new());
+    let lane_split = |id: u64, depth: usize| {
+        let kind = kinds[&id];
+        if kind == StreamKind::Repsig || block_vals.contains(&id) {
+            return Err(bad_split(format!(
+                "{kind} stream {id} crosses the lane boundary and can't be scattered or gathered"
+            )));
+        }
+        Ok(LaneSplit {
+            kind,
+            id,
+            lanes: lanes.iter().map(|lane| lane[&id]).collect(),
+            depth,
+        })
+    };
+    let scatters: Vec<_> = (inputs.difference(&outputs))
+        .map(|&id| lane_split(id, 0))
+        .collect::<Result<_, _>>()?;
+
+    // Follow the fiber levels out from the scattered streams, noting which streams still have
+    // exactly one element or fiber per element of `split`
+    let mut levels: HashMap<u64, (i64, bool)> = (scatters.iter())
+        .map(|scatter| (scatter.id, (0, true)))
+        .collect();
+    let links: Vec<_> = (region.iter())
+        .filter_map(|&index| level_links(uses[index].0))
+        .flatten()
+        .collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for link in &links {
+            if levels.contains_key(&link.output) {
+                continue;
+            }
+            if let Some(&(depth, exact)) = levels.get(&link.input) {
+                let exact = exact && !(link.drops && depth == 0);
+                levels.insert(link.output, (depth + link.offset, exact));
+                changed = true;
+            }
+        }
+    }
+
+    let mut gathers = vec![];
+    // Outputs nothing reads are left as void channels in each lane
+    for &id in outputs.difference(&inputs) {
+        if !consumers.contains_key(&id) {
+            continue;
+        }
+        let depth = match levels.get(&id) {
+            Some(&(depth, true)) if depth >= 0 => depth as usize,
+            Some(&(depth, true)) => {
+                return Err(bad_split(format!(
+                    "stream {id} is reduced to {} fiber levels above it",
+                    -depth
+                )))
+            }
+            Some((_, false)) => {
+                return Err(bad_split(format!(
+                    "stream {id} adds or drops elements of its level, so its lanes can't be put back in order"
+                )))
+            }
+            None => {
+                return Err(bad_split(format!(
+                    "the fiber level of stream {id} can't be traced back to it"
+                )))
+            }
+        };
+        gathers.push(lane_split(id, depth)?);
+    }
+    let plan = ParallelPlan {
+        split,
+        scatters,
+        gathers,
+    };
+
+    let mut operators = vec![];
+    for (index, operation) in graph.operators.iter().enumerate() {
+        if !region.contains(&index) {
+            operators.push(operation.clone());
+            continue;
+        }
+        for lane in &lanes {
+            let mut operation = operation.clone();
+            remap_op(operation.op.as_mut().unwrap(), lane);
+            operators.push(operation);
+        }
+    }
+    let mut comal_graph = comal_graph.clone();
+    comal_graph.graph.as_mut().unwrap().operators = operators;
+    Ok((comal_graph, plan))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parallelize, LaneSplit};
+    use crate::proto_driver::graph_file::{decode_graph, GraphFormat};
+    use crate::proto_driver::validate::{GraphError, StreamKind};
+
+    // Root -> B_i lookup -> B vals, with the coordinates and values written out
+    const GRAPH: &str = r#"{
+        "graph": {
+            "name": "main",
+            "operators": [
+                {"op": {"Root": {"output_ref": {"id": {"id": 1}}}}},
+                {"op": {"FiberLookup": {
+                    "input_ref": {"id": {"id": 1}},
+                    "output_crd": {"id": {"id": 2}},
+                    "output_ref": {"id": {"id": 3}},
+                    "tensor": "B", "mode": 0, "format": "compressed"
+                }}},
+                {"op": {"FiberWrite": {"input_crd": {"id": {"id": 2}}, "tensor": "X", "mode": 0}}},
+                {"op": {"Array": {
+                    "input_ref": {"id": {"id": 3}},
+                    "output_val": {"id": {"id": 4}},
+                    "tensor": "B"
+                }}},
+                {"op": {"ValWrite": {"input_val": {"id": {"id": 4}}, "tensor": "X"}}}
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn parallelize_test() {
+        let graph = decode_graph(GRAPH.as_bytes(), GraphFormat::Json).unwrap();
+        let (parallel, plan) = parallelize(&graph, 3, 2).unwrap();
+
+        // Only the Array is downstream of ref 3, the writers stay single
+        assert_eq!(parallel.graph.unwrap().operators.len(), 6);
+        assert_eq!(plan.split, 3);
+        assert_eq!(
+            plan.scatters,
+            vec![LaneSplit {
+                kind: StreamKind::Ref,
+                id: 3,
+                lanes: vec![5, 7],
+                depth: 0
+            }]
+        );
+        assert_eq!(
+            plan.gathers,
+            vec![LaneSplit {
+                kind: StreamKind::Val,
+                id: 4,
+                lanes: vec![6, 8],
+                depth: 0
+            }]
+        );
+
+        // Splitting at the root puts the gathered coordinates and values one level down
+        let (_, plan) = parallelize(&graph, 1, 2).unwrap();
+        let depths: Vec<_> = (plan.gathers.iter())
+            .map(|gather| (gather.id, gather.depth))
+            .collect();
+        assert_eq!(depths, vec![(2, 1), (4, 1)]);
+    }
+
+    #[test]
+    fn bad_split_test() {
+        let graph = decode_graph(GRAPH.as_bytes(), GraphFormat::Json).unwrap();
+        assert!(matches!(
+            parallelize(&graph, 3, 1),
+            Err(GraphError::BadSplit { id: 3, .. })
+        ));
+        // Val 4 only feeds a writer
+        assert!(matches!(
+            parallelize(&graph, 4, 2),
+            Err(GraphError::BadSplit { id: 4, .. })
+        ));
+    }
+}
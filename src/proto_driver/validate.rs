@@ -0,0 +1,797 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use super::custom::CUSTOM_OPS;
+use super::proto_headers::tortilla::operation::*;
+use super::proto_headers::tortilla::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StreamKind {
+    Crd,
+    Ref,
+    Val,
+    Repsig,
+}
+
+impl Display for StreamKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StreamKind::Crd => write!(f, "crd"),
+            StreamKind::Ref => write!(f, "ref"),
+            StreamKind::Val => write!(f, "val"),
+            StreamKind::Repsig => write!(f, "repsig"),
+        }
+    }
+}
+
+/// Identifies an operator by its position in a graph (or function body) and its type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpRef {
+    pub func: Option<String>,
+    pub index: usize,
+    pub kind: &'static str,
+}
+
+impl OpRef {
+    pub fn new(func: Option<&str>, index: usize, op: Option<&Op>) -> Self {
+        OpRef {
+            func: func.map(str::to_string),
+            index,
+            kind: op.map_or("<empty>", op_kind),
+        }
+    }
+}
+
+impl Display for OpRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} op #{}", self.kind, self.index)?;
+        match &self.func {
+            Some(func) => write!(f, " in function {func:?}"),
+            None => Ok(()),
+        }
+    }
+}
+
+pub fn op_kind(op: &Op) -> &'static str {
+    match op {
+        Op::Broadcast(_) => "Broadcast",
+        Op::Joiner(_) => "Joiner",
+        Op::FiberLookup(_) => "FiberLookup",
+        Op::FiberWrite(_) => "FiberWrite",
+        Op::Repeat(_) => "Repeat",
+        Op::Repeatsig(_) => "Repeatsig",
+        Op::Alu(_) => "Alu",
+        Op::Reduce(_) => "Reduce",
+        Op::CoordHold(_) => "CoordHold",
+        Op::CoordDrop(_) => "CoordDrop",
+        Op::Array(_) => "Array",
+        Op::Spacc(_) => "Spacc",
+        Op::ValWrite(_) => "ValWrite",
+        Op::CoordMask(_) => "CoordMask",
+        Op::Func(_) => "Func",
+        Op::Root(_) => "Root",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphError {
+    MissingGraph,
+    MissingField {
+        op: OpRef,
+        field: &'static str,
+    },
+    NoProducer {
+        op: OpRef,
+        kind: StreamKind,
+        id: u64,
+    },
+    NoConsumer {
+        op: OpRef,
+        kind: StreamKind,
+        id: u64,
+    },
+    DuplicateProducer {
+        op: OpRef,
+        first: OpRef,
+        kind: StreamKind,
+        id: u64,
+    },
+    DuplicateConsumer {
+        op: OpRef,
+        first: OpRef,
+        kind: StreamKind,
+        id: u64,
+    },
+    WrongKind {
+        op: OpRef,
+        first: OpRef,
+        id: u64,
+        expected: StreamKind,
+        found: StreamKind,
+    },
+    UnknownFunction {
+        op: OpRef,
+        name: String,
+    },
+    RecursiveFunction {
+        name: String,
+    },
+    UnboundStream {
+        op: OpRef,
+        kind: StreamKind,
+        id: u64,
+    },
+    UnboundPort {
+        op: OpRef,
+        kind: StreamKind,
+        id: u64,
+    },
+    Unsupported {
+        op: OpRef,
+        reason: String,
+    },
+    BadSplit {
+        id: u64,
+        reason: String,
+    },
+    BadDataDir {
+        path: PathBuf,
+        reason: String,
+    },
+    DataFile {
+        op: OpRef,
+        reason: String,
+    },
+}
+
+impl Display for GraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphError::MissingGraph => write!(f, "ComalGraph has no top-level graph"),
+            GraphError::MissingField { op, field } => write!(f, "{op} is missing field {field:?}"),
+            GraphError::NoProducer { op, kind, id } => {
+                write!(f, "{op} reads {kind} stream {id}, which nothing produces")
+            }
+            GraphError::NoConsumer { op, kind, id } => {
+                write!(f, "{op} writes {kind} stream {id}, which nothing consumes")
+            }
+            GraphError::DuplicateProducer {
+                op,
+                first,
+                kind,
+                id,
+            } => write!(f, "{op} writes {kind} stream {id}, already produced by {first}"),
+            GraphError::DuplicateConsumer {
+                op,
+                first,
+                kind,
+                id,
+            } => write!(f, "{op} reads {kind} stream {id}, already consumed by {first}"),
+            GraphError::WrongKind {
+                op,
+                first,
+                id,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{op} uses stream {id} as a {found} stream, but {first} uses it as a {expected} stream"
+            ),
+            GraphError::UnknownFunction { op, name } => {
+                write!(f, "{op} calls undefined function or custom op {name:?}")
+            }
+            GraphError::RecursiveFunction { name } => {
+                write!(f, "function {name:?} calls itself")
+            }
+            GraphError::UnboundStream { op, kind, id } => write!(
+                f,
+                "{op} binds {kind} stream {id}, which the function body neither reads nor writes"
+            ),
+            GraphError::UnboundPort { op, kind, id } => write!(
+                f,
+                "{op} leaves {kind} stream {id} of the function body unbound"
+            ),
+            GraphError::Unsupported { op, reason } => write!(f, "{op} is unsupported: {reason}"),
+            GraphError::BadSplit { id, reason } => {
+                write!(f, "can't parallelize on stream {id}: {reason}")
+            }
+            GraphError::BadDataDir { path, reason } => {
+                write!(f, "can't open data directory {path:?}: {reason}")
+            }
+            GraphError::DataFile { op, reason } => write!(f, "{op} can't load its data: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Dir {
+    In,
+    Out,
+}
+
+pub(super) struct StreamUse {
+    pub dir: Dir,
+    pub kind: StreamKind,
+    pub id: u64,
+}
+
+trait StreamIdOpt {
+    fn stream_id(&self) -> Option<u64>;
+}
+
+macro_rules! stream_id_impl {
+    ($($stream: ty),*) => {
+        $(
+            impl StreamIdOpt for $stream {
+                fn stream_id(&self) -> Option<u64> {
+                    self.id.as_ref().map(|id| id.id).filter(|id| *id != 0)
+                }
+            }
+
+            impl StreamIdOpt for Option<$stream> {
+                fn stream_id(&self) -> Option<u64> {
+                    self.as_ref().and_then(|stream| stream.stream_id())
+                }
+            }
+        )*
+    };
+}
+
+stream_id_impl!(CrdStream, RefStream, ValStream, RepSigStream);
+
+/// The streams a function body exchanges with its caller, keyed by the body's own stream IDs.
+pub(super) type Boundary = HashMap<(StreamKind, u64), Dir>;
+
+struct Uses<'a> {
+    op: &'a OpRef,
+    uses: Vec<StreamUse>,
+}
+
+impl<'a> Uses<'a> {
+    /// Required inputs that are unset are missing fields.
+    fn input(
+        &mut self,
+        kind: StreamKind,
+        field: &'static str,
+        stream: &impl StreamIdOpt,
+    ) -> Result<(), GraphError> {
+        let id = stream.stream_id().ok_or_else(|| GraphError::MissingField {
+            op: self.op.clone(),
+            field,
+        })?;
+        self.uses.push(StreamUse {
+            dir: Dir::In,
+            kind,
+            id,
+        });
+        Ok(())
+    }
+
+    /// Unset outputs are fine, they're built as void channels.
+    fn output(&mut self, kind: StreamKind, stream: &impl StreamIdOpt) {
+        if let Some(id) = stream.stream_id() {
+            self.uses.push(StreamUse {
+                dir: Dir::Out,
+                kind,
+                id,
+            });
+        }
+    }
+}
+
+/// Every stream an op reads or writes, with Func calls resolved through their callee's boundary.
+pub(super) fn op_streams(
+    op: &Op,
+    opref: &OpRef,
+    boundaries: &HashMap<String, Boundary>,
+) -> Result<Vec<StreamUse>, GraphError> {
+    use StreamKind::*;
+    let missing = |field| GraphError::MissingField {
+        op: opref.clone(),
+        field,
+    };
+    let mut s = Uses {
+        op: opref,
+        uses: vec![],
+    };
+    match op {
+        Op::Broadcast(op) => match op.conn.as_ref().ok_or_else(|| missing("conn"))? {
+            broadcast::Conn::Crd(conn) => {
+                s.input(Crd, "input", &conn.input)?;
+                conn.outputs.iter().for_each(|out| s.output(Crd, out));
+            }
+            broadcast::Conn::Ref(conn) => {
+                s.input(Ref, "input", &conn.input)?;
+                conn.outputs.iter().for_each(|out| s.output(Ref, out));
+            }
+            broadcast::Conn::Val(conn) => {
+                s.input(Val, "input", &conn.input)?;
+                conn.outputs.iter().for_each(|out| s.output(Val, out));
+            }
+            broadcast::Conn::Repsig(conn) => {
+                s.input(Repsig, "input", &conn.input)?;
+                conn.outputs.iter().for_each(|out| s.output(Repsig, out));
+            }
+        },
+        Op::Joiner(op) => {
+            if op.input_pairs.len() < 2 {
+                return Err(missing("input_pairs"));
+            }
+            if op.output_refs.len() != op.input_pairs.len() {
+                return Err(missing("output_refs"));
+            }
+            for pair in &op.input_pairs {
+                s.input(Crd, "input_pairs.crd", &pair.crd)?;
+                s.input(Ref, "input_pairs.ref", &pair.r#ref)?;
+            }
+            op.output_refs.iter().for_each(|out| s.output(Ref, out));
+            s.output(Crd, &op.output_crd);
+        }
+        Op::FiberLookup(op) => {
+            s.input(Ref, "input_ref", &op.input_ref)?;
+            s.output(Crd, &op.output_crd);
+            s.output(Ref, &op.output_ref);
+        }
+        Op::FiberWrite(op) => {
+            s.input(Crd, "input_crd", &op.input_crd)?;
+        }
+        Op::Repeat(op) => {
+            s.input(Ref, "input_ref", &op.input_ref)?;
+            if op.input_rep_crd.stream_id().is_some() {
+                s.input(Crd, "input_rep_crd", &op.input_rep_crd)?;
+            } else {
+                s.input(Ref, "input_rep_ref", &op.input_rep_ref)?;
+            }
+            s.output(Ref, &op.output_ref);
+        }
+        Op::Repeatsig(op) => {
+            s.input(Crd, "input_crd", &op.input_crd)?;
+            s.output(Repsig, &op.output_rep_sig);
+        }
+        Op::Alu(op) => {
+            if op.stages.is_empty() {
+                return Err(missing("stages"));
+            }
+            match op.conn.as_ref().ok_or_else(|| missing("conn"))? {
+                alu::Conn::Vals(conn) => {
+                    if conn.inputs.is_empty() {
+                        return Err(missing("inputs"));
+                    }
+                    for input in &conn.inputs {
+                        s.input(Val, "inputs", input)?;
+                    }
+                    s.output(Val, &conn.output);
+                }
+                alu::Conn::Crds(conn) => {
+                    if conn.inputs.is_empty() {
+                        return Err(missing("inputs"));
+                    }
+                    for input in &conn.inputs {
+                        s.input(Crd, "inputs", input)?;
+                    }
+                    s.output(Crd, &conn.output);
+                }
+            }
+        }
+        Op::Reduce(op) => {
+            s.input(Val, "input_val", &op.input_val)?;
+            s.output(Val, &op.output_val);
+        }
+        Op::CoordHold(op) => {
+            s.input(Crd, "input_inner_crd", &op.input_inner_crd)?;
+            s.input(Crd, "input_outer_crd", &op.input_outer_crd)?;
+            s.output(Crd, &op.output_inner_crd);
+            s.output(Crd, &op.output_outer_crd);
+        }
+        Op::CoordDrop(op) => {
+            s.input(Crd, "input_inner_crd", &op.input_inner_crd)?;
+            s.input(Crd, "input_outer_crd", &op.input_outer_crd)?;
+            s.output(Crd, &op.output_inner_crd);
+            s.output(Crd, &op.output_outer_crd);
+        }
+        Op::Array(op) => {
+            s.input(Ref, "input_ref", &op.input_ref)?;
+            s.output(Val, &op.output_val);
+        }
+        Op::Spacc(op) => {
+            if op.input_outer_crds.is_empty() {
+                return Err(missing("input_outer_crds"));
+            }
+            s.input(Crd, "input_inner_crd", &op.input_inner_crd)?;
+            for input in &op.input_outer_crds {
+                s.input(Crd, "input_outer_crds", input)?;
+            }
+            s.input(Val, "input_val", &op.input_val)?;
+            s.output(Crd, &op.output_inner_crd);
+            op.output_outer_crds
+                .iter()
+                .for_each(|out| s.output(Crd, out));
+            s.output(Val, &op.output_val);
+        }
+        Op::ValWrite(op) => {
+            s.input(Val, "input_val", &op.input_val)?;
+        }
+        Op::CoordMask(op) => {
+            s.input(Crd, "input_inner_crd", &op.input_inner_crd)?;
+            s.input(Crd, "input_outer_crd", &op.input_outer_crd)?;
+            s.input(Ref, "input_ref", &op.input_ref)?;
+            s.output(Crd, &op.output_inner_crd);
+            s.output(Crd, &op.output_outer_crd);
+            s.output(Ref, &op.output_ref);
+        }
+        Op::Func(op) => {
+            let boundary = boundaries
+                .get(&op.name)
+                .ok_or_else(|| GraphError::UnknownFunction {
+                    op: opref.clone(),
+                    name: op.name.clone(),
+                })?;
+            let all_bindings = [
+                (Ref, &op.ref_bindings),
+                (Crd, &op.crd_bindings),
+                (Val, &op.val_bindings),
+                (Repsig, &op.repsig_bindings),
+            ];
+            // Every port needs a caller-side stream, or the body's end of it is left dangling
+            for (kind, bindings) in all_bindings {
+                let unbound = (boundary.keys())
+                    .filter(|(port_kind, id)| *port_kind == kind && !bindings.contains_key(id))
+                    .map(|(_, id)| *id)
+                    .min();
+                if let Some(id) = unbound {
+                    return Err(GraphError::UnboundPort {
+                        op: opref.clone(),
+                        kind,
+                        id,
+                    });
+                }
+            }
+            for (kind, bindings) in all_bindings {
+                for (inner, outer) in bindings {
+                    let dir =
+                        boundary
+                            .get(&(kind, *inner))
+                            .ok_or_else(|| GraphError::UnboundStream {
+                                op: opref.clone(),
+                                kind,
+                                id: *inner,
+                            })?;
+                    s.uses.push(StreamUse {
+                        dir: *dir,
+                        kind,
+                        id: *outer,
+                    });
+                }
+            }
+        }
+        Op::Root(op) => {
+            s.output(Ref, &op.output_ref);
+        }
+    }
+    Ok(s.uses)
+}
+
+/// Checks the streams of a single graph. Function bodies are open: whatever they read without producing
+/// (or produce without reading) is returned as their boundary with the caller.
+fn check_graph(
+    graph: &ProgramGraph,
+    func: Option<&str>,
+    boundaries: &HashMap<String, Boundary>,
+    is_bound: &dyn Fn(StreamKind, u64) -> bool,
+    require_consumers: bool,
+) -> Result<Boundary, GraphError> {
+    let mut kinds: HashMap<u64, (StreamKind, OpRef)> = HashMap::new();
+    let mut producers: HashMap<u64, OpRef> = HashMap::new();
+    let mut consumers: HashMap<u64, OpRef> = HashMap::new();
+
+    for (index, operation) in graph.operators.iter().enumerate() {
+        let opref = OpRef::new(func, index, operation.op.as_ref());
+        let op = operation
+            .op
+            .as_ref()
+            .ok_or_else(|| GraphError::MissingField {
+                op: opref.clone(),
+                field: "op",
+            })?;
+        for stream in op_streams(op, &opref, boundaries)? {
+            match kinds.get(&stream.id) {
+                Some((kind, first)) if *kind != stream.kind => {
+                    return Err(GraphError::WrongKind {
+                        op: opref,
+                        first: first.clone(),
+                        id: stream.id,
+                        expected: *kind,
+                        found: stream.kind,
+                    });
+                }
+                Some(_) => (),
+                None => {
+                    kinds.insert(stream.id, (stream.kind, opref.clone()));
+                }
+            }
+            let (users, duplicate): (_, fn(OpRef, OpRef, StreamKind, u64) -> GraphError) =
+                match stream.dir {
+                    Dir::Out => (&mut producers, |op, first, kind, id| {
+                        GraphError::DuplicateProducer {
+                            op,
+                            first,
+                            kind,
+                            id,
+                        }
+                    }),
+                    Dir::In => (&mut consumers, |op, first, kind, id| {
+                        GraphError::DuplicateConsumer {
+                            op,
+                            first,
+                            kind,
+                            id,
+                        }
+                    }),
+                };
+            if let Some(first) = users.get(&stream.id) {
+                return Err(duplicate(opref, first.clone(), stream.kind, stream.id));
+            }
+            users.insert(stream.id, opref.clone());
+        }
+    }
+
+    let mut boundary = Boundary::new();
+    for (id, op) in &consumers {
+        let kind = kinds[id].0;
+        if producers.contains_key(id) {
+            continue;
+        }
+        if func.is_some() {
+            boundary.insert((kind, *id), Dir::In);
+        } else if !is_bound(kind, *id) {
+            return Err(GraphError::NoProducer {
+                op: op.clone(),
+                kind,
+                id: *id,
+            });
+        }
+    }
+    for (id, op) in &producers {
+        let kind = kinds[id].0;
+        if consumers.contains_key(id) {
+            continue;
+        }
+        if func.is_some() {
+            boundary.insert((kind, *id), Dir::Out);
+        } else if require_consumers {
+            return Err(GraphError::NoConsumer {
+                op: op.clone(),
+                kind,
+                id: *id,
+            });
+        }
+    }
+    Ok(boundary)
+}
+
+/// Checks the whole graph, including every function body, before any context is built.
+/// `is_bound` reports streams whose producer lives outside the graph (e.g. channels the caller already set up).
+/// With `require_consumers`, streams that nothing reads are an error instead of being left to the caller.
+pub fn validate(
+    comal_graph: &ComalGraph,
+    is_bound: impl Fn(StreamKind, u64) -> bool,
+    require_consumers: bool,
+) -> Result<(), GraphError> {
+    let boundaries = function_boundaries(comal_graph)?;
+    let graph = comal_graph.graph.as_ref().ok_or(GraphError::MissingGraph)?;
+    check_graph(graph, None, &boundaries, &is_bound, require_consumers)?;
+    Ok(())
+}
+
+/// Checks every function body and returns the boundary of each, keyed by function name.
+pub(super) fn function_boundaries(
+    comal_graph: &ComalGraph,
+) -> Result<HashMap<String, Boundary>, GraphError> {
+    let names: HashSet<&str> = comal_graph
+        .funcs
+        .iter()
+        .map(|func| func.name.as_str())
+        .collect();
+    let callees = |func: &ProgramGraph| -> Vec<String> {
+        (func.operators.iter())
+            .filter_map(|operation| match &operation.op {
+                Some(Op::Func(op)) if names.contains(op.name.as_str()) => Some(op.name.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // Function bodies are checked callees first, so every call site knows its callee's boundary.
+    // Registered custom ops declare theirs up front, and a function body of the same name wins.
+    let mut boundaries: HashMap<String, Boundary> = (CUSTOM_OPS.iter())
+        .filter(|op| !names.contains(op.name))
+        .map(|op| (op.name.to_string(), op.boundary()))
+        .collect();
+    let mut pending: Vec<&ProgramGraph> = comal_graph.funcs.iter().collect();
+    while !pending.is_empty() {
+        let (ready, waiting): (Vec<_>, Vec<_>) = pending.into_iter().partition(|func| {
+            callees(func)
+                .iter()
+                .all(|callee| boundaries.contains_key(callee))
+        });
+        if ready.is_empty() {
+            return Err(GraphError::RecursiveFunction {
+                name: waiting[0].name.clone(),
+            });
+        }
+        for func in ready {
+            let boundary = check_graph(func, Some(&func.name), &boundaries, &|_, _| true, false)?;
+            boundaries.insert(func.name.clone(), boundary);
+        }
+        pending = waiting;
+    }
+    Ok(boundaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate, GraphError, StreamKind};
+    use crate::proto_driver::graph_file::{decode_graph, GraphFormat};
+
+    fn check(json: &str) -> Result<(), GraphError> {
+        let graph = decode_graph(json.as_bytes(), GraphFormat::Json).unwrap();
+        validate(&graph, |_, _| false, true)
+    }
+
+    fn with_operators(operators: &str) -> String {
+        format!(r#"{{"graph": {{"name": "main", "operators": [{operators}]}}}}"#)
+    }
+
+    #[test]
+    fn no_producer_test() {
+        let graph = with_operators(
+            r#"{"op": {"ValWrite": {"input_val": {"id": {"id": 5}}, "tensor": "X"}}}"#,
+        );
+        assert!(matches!(
+            check(&graph),
+            Err(GraphError::NoProducer {
+                kind: StreamKind::Val,
+                id: 5,
+                ..
+            })
+        ));
+
+        // Streams the caller already set up count as produced
+        let graph = decode_graph(graph.as_bytes(), GraphFormat::Json).unwrap();
+        let is_bound = |kind, id| kind == StreamKind::Val && id == 5;
+        assert_eq!(validate(&graph, is_bound, true), Ok(()));
+    }
+
+    #[test]
+    fn duplicate_producer_test() {
+        let graph = with_operators(
+            r#"
+            {"op": {"Root": {"output_ref": {"id": {"id": 1}}}}},
+            {"op": {"Root": {"output_ref": {"id": {"id": 1}}}}},
+            {"op": {"Array": {
+                "input_ref": {"id": {"id": 1}},
+                "output_val": {"id": {"id": 2}},
+                "tensor": "B"
+            }}},
+            {"op": {"ValWrite": {"input_val": {"id": {"id": 2}}, "tensor": "X"}}}
+            "#,
+        );
+        match check(&graph) {
+            Err(GraphError::DuplicateProducer {
+                op,
+                first,
+                kind,
+                id,
+            }) => {
+                assert_eq!((kind, id), (StreamKind::Ref, 1));
+                assert_eq!((first.index, op.index), (0, 1));
+            }
+            other => panic!("expected a duplicate producer, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn missing_field_test() {
+        let graph = with_operators(
+            r#"
+            {"op": {"FiberLookup": {
+                "output_crd": {"id": {"id": 2}},
+                "tensor": "B", "mode": 0, "format": "compressed"
+            }}}
+            "#,
+        );
+        assert!(matches!(
+            check(&graph),
+            Err(GraphError::MissingField {
+                field: "input_ref",
+                ..
+            })
+        ));
+
+        let graph = with_operators("{}");
+        assert!(matches!(
+            check(&graph),
+            Err(GraphError::MissingField { field: "op", .. })
+        ));
+    }
+
+    #[test]
+    fn unknown_function_test() {
+        let graph = with_operators(r#"{"op": {"Func": {"name": "undefined"}}}"#);
+        match check(&graph) {
+            Err(GraphError::UnknownFunction { name, .. }) => assert_eq!(name, "undefined"),
+            other => panic!("expected an unknown function, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn recursive_function_test() {
+        let call = |name: &str| format!(r#"{{"op": {{"Func": {{"name": "{name}"}}}}}}"#);
+        let graph = format!(
+            r#"{{
+                "graph": {{"name": "main", "operators": [{}]}},
+                "funcs": [
+                    {{"name": "f", "operators": [{}]}},
+                    {{"name": "g", "operators": [{}]}},
+                    {{"name": "h", "operators": [{}]}}
+                ]
+            }}"#,
+            call("f"),
+            call("g"),
+            call("f"),
+            call("h"),
+        );
+        // f and g call each other, h calls itself
+        assert!(matches!(
+            check(&graph),
+            Err(GraphError::RecursiveFunction { name }) if name == "f"
+        ));
+    }
+
+    #[test]
+    fn function_binding_test() {
+        // f reads ref 1 and writes val 2
+        let graph = |bindings: &str| {
+            format!(
+                r#"{{
+                "graph": {{"name": "main", "operators": [
+                    {{"op": {{"Root": {{"output_ref": {{"id": {{"id": 1}}}}}}}}}},
+                    {{"op": {{"Func": {{"name": "f", {bindings}}}}}}},
+                    {{"op": {{"ValWrite": {{"input_val": {{"id": {{"id": 2}}}}, "tensor": "X"}}}}}}
+                ]}},
+                "funcs": [{{"name": "f", "operators": [
+                    {{"op": {{"Array": {{
+                        "input_ref": {{"id": {{"id": 1}}}},
+                        "output_val": {{"id": {{"id": 2}}}},
+                        "tensor": "B"
+                    }}}}}}
+                ]}}]
+            }}"#
+            )
+        };
+        assert_eq!(
+            check(&graph(
+                r#""ref_bindings": {"1": 1}, "val_bindings": {"2": 2}"#
+            )),
+            Ok(())
+        );
+        match check(&graph(r#""ref_bindings": {"1": 1}"#)) {
+            Err(GraphError::UnboundPort { kind, id, .. }) => {
+                assert_eq!((kind, id), (StreamKind::Val, 2))
+            }
+            other => panic!("expected an unbound port, got {other:?}"),
+        }
+        // The body has no crd port 3
+        match check(&graph(
+            r#""ref_bindings": {"1": 1}, "crd_bindings": {"3": 3}, "val_bindings": {"2": 2}"#,
+        )) {
+            Err(GraphError::UnboundStream { kind, id, .. }) => {
+                assert_eq!((kind, id), (StreamKind::Crd, 3))
+            }
+            other => panic!("expected an unbound stream, got {other:?}"),
+        }
+    }
+}
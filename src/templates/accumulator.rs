@@ -231,6 +231,196 @@ where
     }
 }
 
+/// Accumulator over a multi-level fiber tree: every coordinate level in `in_crds` is kept
+/// (outermost first) while the fibers under each `in_crd_reduce` coordinate are summed together.
+pub struct SpaccNData<CrdType: Clone, ValType: Clone, StopType: Clone> {
+    pub in_val: Receiver<Token<ValType, StopType>>,
+    pub in_crd_reduce: Receiver<Token<CrdType, StopType>>,
+    pub in_crds: Vec<Receiver<Token<CrdType, StopType>>>,
+    pub out_val: Sender<Token<ValType, StopType>>,
+    pub out_crds: Vec<Sender<Token<CrdType, StopType>>>,
+}
+
+#[context_macro]
+pub struct SpaccN<CrdType: Clone, ValType: Clone, StopType: Clone> {
+    spacc_data: SpaccNData<CrdType, ValType, StopType>,
+}
+
+impl<CrdType: DAMType, ValType: DAMType, StopType: DAMType> SpaccN<CrdType, ValType, StopType>
+where
+    SpaccN<CrdType, ValType, StopType>: Context,
+{
+    pub fn new(spacc_data: SpaccNData<CrdType, ValType, StopType>) -> Self {
+        assert!(
+            !spacc_data.in_crds.is_empty(),
+            "SpaccN needs at least one kept coordinate level"
+        );
+        assert_eq!(spacc_data.in_crds.len(), spacc_data.out_crds.len());
+        let red = SpaccN {
+            spacc_data,
+            context_info: Default::default(),
+        };
+        (red.spacc_data.in_crd_reduce).attach_receiver(&red);
+        red.spacc_data
+            .in_crds
+            .iter()
+            .for_each(|in_crd| in_crd.attach_receiver(&red));
+        (red.spacc_data.in_val).attach_receiver(&red);
+        red.spacc_data
+            .out_crds
+            .iter()
+            .for_each(|out_crd| out_crd.attach_sender(&red));
+        (red.spacc_data.out_val).attach_sender(&red);
+
+        red
+    }
+}
+
+impl<CrdType, ValType, StopType> SpaccN<CrdType, ValType, StopType>
+where
+    CrdType: DAMType + std::cmp::Ord,
+    ValType: DAMType,
+    StopType: DAMType + std::ops::Add<u32, Output = StopType>,
+{
+    fn emit_crd(&self, level: usize, tkn: Token<CrdType, StopType>) {
+        let chan_elem = ChannelElement::new(self.time.tick() + 1, tkn);
+        self.spacc_data.out_crds[level]
+            .enqueue(&self.time, chan_elem)
+            .unwrap();
+    }
+
+    fn emit_val(&self, tkn: Token<ValType, StopType>) {
+        let chan_elem = ChannelElement::new(self.time.tick() + 1, tkn);
+        self.spacc_data
+            .out_val
+            .enqueue(&self.time, chan_elem)
+            .unwrap();
+    }
+
+    fn emit_stop(&self, level: usize, stkn: StopType) {
+        self.emit_crd(level, Token::Stop(stkn.clone()));
+        if level == self.spacc_data.out_crds.len() - 1 {
+            self.emit_val(Token::Stop(stkn));
+        }
+    }
+
+    /// Writes out the accumulated tree as a well-formed multi-level stream, closing the
+    /// outermost level with `stkn` and every level below it one stop level higher.
+    fn flush(&self, accum_storage: &BTreeMap<Vec<CrdType>, ValType>, stkn: StopType) {
+        let num_levels = self.spacc_data.out_crds.len();
+        let mut prev_key: Option<&Vec<CrdType>> = None;
+        for (key, value) in accum_storage {
+            let first_diff = match prev_key {
+                Some(prev) => {
+                    let first_diff = (0..num_levels).find(|&l| prev[l] != key[l]).unwrap();
+                    for level in first_diff + 1..num_levels {
+                        let stop_lvl: u32 = (level - first_diff - 1).try_into().unwrap();
+                        self.emit_stop(level, StopType::default() + stop_lvl);
+                    }
+                    first_diff
+                }
+                None => 0,
+            };
+            for (level, crd) in key.iter().enumerate().skip(first_diff) {
+                self.emit_crd(level, Token::Val(crd.clone()));
+            }
+            self.emit_val(Token::Val(value.clone()));
+            prev_key = Some(key);
+        }
+        for level in 0..num_levels {
+            let stop_lvl: u32 = level.try_into().unwrap();
+            self.emit_stop(level, stkn.clone() + stop_lvl);
+        }
+    }
+}
+
+impl<CrdType, ValType, StopType> Context for SpaccN<CrdType, ValType, StopType>
+where
+    CrdType: DAMType + std::cmp::Ord,
+    ValType: DAMType + std::ops::AddAssign<ValType>,
+    StopType: DAMType + std::ops::Add<u32, Output = StopType> + std::cmp::PartialEq,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) {
+        let num_levels = self.spacc_data.in_crds.len();
+        let mut accum_storage: BTreeMap<Vec<CrdType>, ValType> = BTreeMap::new();
+        // Coordinates of the enclosing fibers at the level currently being read
+        let mut prefix: Vec<CrdType> = Vec::with_capacity(num_levels);
+        loop {
+            let in_rcrd = self.spacc_data.in_crd_reduce.peek_next(&self.time).unwrap();
+            match in_rcrd.data {
+                Token::Val(_) => {
+                    let level = prefix.len();
+                    let innermost = level == num_levels - 1;
+                    let in_crd = self.spacc_data.in_crds[level]
+                        .peek_next(&self.time)
+                        .unwrap();
+                    match in_crd.data {
+                        Token::Val(crd) if innermost => {
+                            match self.spacc_data.in_val.peek_next(&self.time).unwrap().data {
+                                Token::Val(val) => {
+                                    let mut key = prefix.clone();
+                                    key.push(crd);
+                                    *accum_storage.entry(key).or_default() += val;
+                                }
+                                _ => {
+                                    panic!("Invalid token found");
+                                }
+                            }
+                            self.spacc_data.in_val.dequeue(&self.time).unwrap();
+                        }
+                        Token::Val(crd) => {
+                            prefix.push(crd);
+                        }
+                        Token::Stop(crd_stkn) => {
+                            if innermost {
+                                match self.spacc_data.in_val.peek_next(&self.time).unwrap().data {
+                                    Token::Stop(val_stkn) => assert_eq!(val_stkn, crd_stkn),
+                                    _ => {
+                                        panic!("Stop tokens must match for inner crd");
+                                    }
+                                }
+                                self.spacc_data.in_val.dequeue(&self.time).unwrap();
+                            }
+                            if level == 0 {
+                                self.spacc_data.in_crd_reduce.dequeue(&self.time).unwrap();
+                            } else {
+                                prefix.pop();
+                            }
+                        }
+                        Token::Empty => {
+                            if innermost {
+                                self.spacc_data.in_val.dequeue(&self.time).unwrap();
+                            }
+                        }
+                        Token::Done => {
+                            panic!("Reached Done too soon");
+                        }
+                    }
+                    self.spacc_data.in_crds[level].dequeue(&self.time).unwrap();
+                }
+                Token::Stop(stkn) => {
+                    self.flush(&accum_storage, stkn);
+                    accum_storage.clear();
+                    self.spacc_data.in_crd_reduce.dequeue(&self.time).unwrap();
+                }
+                Token::Done => {
+                    for level in 0..num_levels {
+                        self.emit_crd(level, Token::Done);
+                    }
+                    self.emit_val(Token::Done);
+                    return;
+                }
+                _ => {
+                    panic!("Unexpected empty token found");
+                }
+            }
+            self.time.incr_cycles(1);
+        }
+    }
+}
+
 #[context_macro]
 pub struct MaxReduce<ValType: Clone, StopType: Clone> {
     max_reduce_data: ReduceData<ValType, StopType>,
@@ -327,8 +517,8 @@ mod tests {
     use crate::templates::primitive::Token;
     use crate::token_vec;
 
-    use super::{MaxReduce, Reduce, Spacc1};
-    use super::{ReduceData, Spacc1Data};
+    use super::{MaxReduce, Reduce, Spacc1, SpaccN};
+    use super::{ReduceData, Spacc1Data, SpaccNData};
 
     #[test]
     fn reduce_2d_test() {
@@ -356,6 +546,26 @@ mod tests {
         spacc1_test(in_ocrd, in_icrd, in_val, out_icrd, out_val);
     }
 
+    #[test]
+    fn spacc2_3d_test() {
+        // Two reduction coordinates, each holding a 2-level (j, l) fiber tree
+        let in_rcrd = || token_vec!(u32; u32; 0, 1, "S0", "D").into_iter();
+        let in_jcrd = || token_vec!(u32; u32; 0, 2, "S0", 1, 2, "S1", "D").into_iter();
+        let in_lcrd =
+            || token_vec!(u32; u32; 1, 3, "S0", 0, "S1", 4, "S0", 0, 1, "S2", "D").into_iter();
+        let in_val = || {
+            token_vec!(f32; u32; 1.0, 2.0, "S0", 3.0, "S1", 4.0, "S0", 5.0, 6.0, "S2", "D")
+                .into_iter()
+        };
+        let out_jcrd = || token_vec!(u32; u32; 0, 1, 2, "S0", "D").into_iter();
+        let out_lcrd = || token_vec!(u32; u32; 1, 3, "S0", 4, "S0", 0, 1, "S1", "D").into_iter();
+        let out_val =
+            || token_vec!(f32; u32; 1.0, 2.0, "S0", 4.0, "S0", 8.0, 6.0, "S1", "D").into_iter();
+        spacc2_test(
+            in_rcrd, in_jcrd, in_lcrd, in_val, out_jcrd, out_lcrd, out_val,
+        );
+    }
+
     #[test]
     fn max_reduce_2d_test() {
         let in_val = || {
@@ -436,6 +646,55 @@ mod tests {
         dbg!(executed.elapsed_cycles());
     }
 
+    #[allow(clippy::too_many_arguments)]
+    fn spacc2_test<IRT1, IRT2, IRT3, IRT4, ORT1, ORT2, ORT3>(
+        in_rcrd: fn() -> IRT1,
+        in_jcrd: fn() -> IRT2,
+        in_lcrd: fn() -> IRT3,
+        in_val: fn() -> IRT4,
+        out_jcrd: fn() -> ORT1,
+        out_lcrd: fn() -> ORT2,
+        out_val: fn() -> ORT3,
+    ) where
+        IRT1: Iterator<Item = Token<u32, u32>> + 'static,
+        IRT2: Iterator<Item = Token<u32, u32>> + 'static,
+        IRT3: Iterator<Item = Token<u32, u32>> + 'static,
+        IRT4: Iterator<Item = Token<f32, u32>> + 'static,
+        ORT1: Iterator<Item = Token<u32, u32>> + 'static,
+        ORT2: Iterator<Item = Token<u32, u32>> + 'static,
+        ORT3: Iterator<Item = Token<f32, u32>> + 'static,
+    {
+        let mut parent = ProgramBuilder::default();
+        let (in_rcrd_sender, in_rcrd_receiver) = parent.unbounded();
+        let (in_jcrd_sender, in_jcrd_receiver) = parent.unbounded();
+        let (in_lcrd_sender, in_lcrd_receiver) = parent.unbounded();
+        let (in_val_sender, in_val_receiver) = parent.unbounded();
+        let (out_jcrd_sender, out_jcrd_receiver) = parent.unbounded();
+        let (out_lcrd_sender, out_lcrd_receiver) = parent.unbounded();
+        let (out_val_sender, out_val_receiver) = parent.unbounded();
+        let data = SpaccNData::<u32, f32, u32> {
+            in_val: in_val_receiver,
+            in_crd_reduce: in_rcrd_receiver,
+            in_crds: vec![in_jcrd_receiver, in_lcrd_receiver],
+            out_val: out_val_sender,
+            out_crds: vec![out_jcrd_sender, out_lcrd_sender],
+        };
+        let red = SpaccN::new(data);
+        parent.add_child(GeneratorContext::new(in_rcrd, in_rcrd_sender));
+        parent.add_child(GeneratorContext::new(in_jcrd, in_jcrd_sender));
+        parent.add_child(GeneratorContext::new(in_lcrd, in_lcrd_sender));
+        parent.add_child(GeneratorContext::new(in_val, in_val_sender));
+        parent.add_child(CheckerContext::new(out_jcrd, out_jcrd_receiver));
+        parent.add_child(CheckerContext::new(out_lcrd, out_lcrd_receiver));
+        parent.add_child(CheckerContext::new(out_val, out_val_receiver));
+        parent.add_child(red);
+        let executed = parent
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+    }
+
     fn max_reduce_test<IRT, ORT>(in_val: fn() -> IRT, out_val: fn() -> ORT)
     where
         IRT: Iterator<Item = Token<f32, u32>> + 'static,
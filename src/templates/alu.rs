@@ -109,16 +109,168 @@ pub fn make_unary_alu<ValType: DAMType, StopType: DAMType>(
     pcu
 }
 
+/// A stage of a fused ALU chain, tagged with how many operands it reads.
+pub enum ChainedOp<T> {
+    Unary(ALUOp<T>),
+    Binary(ALUOp<T>),
+}
+
+impl<T> ChainedOp<T> {
+    pub fn arity(&self) -> usize {
+        match self {
+            ChainedOp::Unary(_) => 1,
+            ChainedOp::Binary(_) => 2,
+        }
+    }
+}
+
+/// A stage of a fused ALU chain and the values it reads, in operand order.
+/// Values `0..n` are the chain's `n` inputs and value `n + k` is the result of stage `k`.
+pub struct ChainedStage<T> {
+    pub op: ChainedOp<T>,
+    pub operands: Vec<usize>,
+}
+
+/// Operands for chains that don't spell them out: the first stage reads the first input (and the
+/// second input if binary), and every later binary stage combines the running result with the next
+/// unused input, e.g. `(a * b) + c`.
+pub fn left_fold_operands(
+    arities: impl IntoIterator<Item = usize>,
+    num_inputs: usize,
+) -> Vec<Vec<usize>> {
+    let mut next_input = 0;
+    arities
+        .into_iter()
+        .enumerate()
+        .map(|(stage, arity)| {
+            let mut operands = match stage {
+                0 => vec![],
+                _ => vec![num_inputs + stage - 1],
+            };
+            while operands.len() < arity {
+                operands.push(next_input);
+                next_input += 1;
+            }
+            operands
+        })
+        .collect()
+}
+
+/// Checks that every stage reads as many values as its op takes, only reads inputs and the results
+/// of earlier stages, and that every input and intermediate result is used.
+pub fn check_chain(num_inputs: usize, stages: &[(usize, Vec<usize>)]) -> Result<(), String> {
+    if stages.is_empty() {
+        return Err("an ALU chain needs at least one stage".to_string());
+    }
+    let mut used = vec![false; num_inputs + stages.len()];
+    for (stage, (arity, operands)) in stages.iter().enumerate() {
+        if operands.len() != *arity {
+            return Err(format!(
+                "stage {stage} reads {} value(s), but its op takes {arity}",
+                operands.len()
+            ));
+        }
+        for &operand in operands {
+            if operand >= num_inputs + stage {
+                return Err(format!(
+                    "stage {stage} reads value {operand}, which isn't an input or an earlier result"
+                ));
+            }
+            used[operand] = true;
+        }
+    }
+    // The last stage's result is the output
+    used[num_inputs + stages.len() - 1] = true;
+    match used.iter().position(|used| !used) {
+        Some(value) if value < num_inputs => Err(format!("input {value} is never read")),
+        Some(value) => Err(format!(
+            "the result of stage {} is never read",
+            value - num_inputs
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Builds a single multi-stage PCU out of a chain of ops.
+/// Register `v` holds value `v` of the chain: the inputs come in through the first registers, each stage
+/// writes its result to its own register, and values still needed downstream are forwarded through the
+/// pipeline. The last stage's result is written out.
+pub fn make_chained_alu<ValType: DAMType, StopType: DAMType>(
+    args: Vec<Receiver<Token<ValType, StopType>>>,
+    res: Sender<Token<ValType, StopType>>,
+    stages: Vec<ChainedStage<Token<ValType, StopType>>>,
+) -> impl Context {
+    let num_inputs = args.len();
+    let shape: Vec<_> = (stages.iter())
+        .map(|stage| (stage.op.arity(), stage.operands.clone()))
+        .collect();
+    if let Err(err) = check_chain(num_inputs, &shape) {
+        panic!("Invalid ALU chain: {err}");
+    }
+    // The last stage that reads each value
+    let mut last_use = vec![0; num_inputs + stages.len()];
+    for (stage, (_, operands)) in shape.iter().enumerate() {
+        operands
+            .iter()
+            .for_each(|&operand| last_use[operand] = stage);
+    }
+
+    let ingress_op = PCU::<Token<ValType, StopType>>::READ_ALL_INPUTS;
+    let egress_op = PCU::<Token<ValType, StopType>>::WRITE_ALL_RESULTS;
+
+    let num_stages = stages.len();
+    let mut pcu = PCU::new(
+        PCUConfig {
+            pipeline_depth: num_stages.try_into().unwrap(),
+            num_registers: num_inputs + num_stages,
+        },
+        ingress_op,
+        egress_op,
+    );
+
+    for (index, stage) in stages.into_iter().enumerate() {
+        let op = match stage.op {
+            ChainedOp::Unary(op) | ChainedOp::Binary(op) => op,
+        };
+        let last = index + 1 == num_stages;
+        pcu.push_stage(PipelineStage {
+            op,
+            forward: (0..num_inputs + index)
+                .filter(|&value| last_use[value] > index)
+                .map(|value| (value, value))
+                .collect(),
+            prev_register_ids: stage.operands,
+            next_register_ids: if last {
+                vec![]
+            } else {
+                vec![num_inputs + index]
+            },
+            output_register_ids: if last { vec![0] } else { vec![] },
+        });
+    }
+    args.into_iter().for_each(|arg| pcu.add_input_channel(arg));
+    pcu.add_output_channel(res);
+
+    pcu
+}
+
 #[cfg(test)]
 mod tests {
     use dam::simulation::{InitializationOptions, ProgramBuilder, RunOptions};
 
-    use dam::{templates::ops::ALUAddOp, utility_contexts::*};
+    use dam::{
+        templates::ops::{ALUAddOp, ALUMulOp, ALUSubOp},
+        utility_contexts::*,
+    };
 
-    use crate::templates::primitive::{ALUExpOp, Exp, Token};
+    use crate::templates::primitive::{ALUExpOp, ALUSigmoidOp, Exp, Sigmoid, Token};
+    use crate::templates::tensor::Tensor;
     use crate::token_vec;
 
-    use super::{make_alu, make_unary_alu};
+    use super::{
+        check_chain, left_fold_operands, make_alu, make_chained_alu, make_unary_alu, ChainedOp,
+        ChainedStage,
+    };
 
     #[test]
     fn add_test() {
@@ -200,4 +352,203 @@ mod tests {
             .run(RunOptions::default());
         dbg!(executed.elapsed_cycles());
     }
+
+    #[test]
+    fn sigmoid_test() {
+        let mut parent = ProgramBuilder::default();
+        let (arg1_send, arg1_recv) = parent.unbounded::<Token<f32, u32>>();
+        let (pcu_out_send, pcu_out_recv) = parent.unbounded::<Token<f32, u32>>();
+        let unary_alu = make_unary_alu(arg1_recv, pcu_out_send, ALUSigmoidOp());
+        let gen1 = GeneratorContext::new(
+            || token_vec!(f32; u32; 0.0, -2.0, 3.0, "S0", 1.0, "S1", "D").into_iter(),
+            arg1_send,
+        );
+        let checker = CheckerContext::new(
+            || {
+                token_vec!(f32; u32; 0.0, -2.0, 3.0, "S0", 1.0, "S1", "D")
+                    .into_iter()
+                    .map(|a| a.sigmoid())
+            },
+            pcu_out_recv,
+        );
+        parent.add_child(gen1);
+        parent.add_child(unary_alu);
+        parent.add_child(checker);
+        let executed = parent
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+    }
+
+    #[test]
+    fn chained_alu_test() {
+        let mut parent = ProgramBuilder::default();
+        let (arg1_send, arg1_recv) = parent.unbounded::<Token<f32, u32>>();
+        let (arg2_send, arg2_recv) = parent.unbounded::<Token<f32, u32>>();
+        let (arg3_send, arg3_recv) = parent.unbounded::<Token<f32, u32>>();
+        let (pcu_out_send, pcu_out_recv) = parent.unbounded::<Token<f32, u32>>();
+
+        // exp(a * b + c)
+        let ops = vec![
+            ChainedOp::Binary(ALUMulOp()),
+            ChainedOp::Binary(ALUAddOp()),
+            ChainedOp::Unary(ALUExpOp()),
+        ];
+        let operands = left_fold_operands(ops.iter().map(ChainedOp::arity), 3);
+        assert_eq!(operands, vec![vec![0, 1], vec![3, 2], vec![4]]);
+        let stages = (ops.into_iter().zip(operands))
+            .map(|(op, operands)| ChainedStage { op, operands })
+            .collect();
+        let alu = make_chained_alu(vec![arg1_recv, arg2_recv, arg3_recv], pcu_out_send, stages);
+        let gen1 = GeneratorContext::new(
+            || token_vec!(f32; u32; 1.0, 2.0, 0.5, "S0", "D").into_iter(),
+            arg1_send,
+        );
+        let gen2 = GeneratorContext::new(
+            || token_vec!(f32; u32; 2.0, 0.5, 4.0, "S0", "D").into_iter(),
+            arg2_send,
+        );
+        let gen3 = GeneratorContext::new(
+            || token_vec!(f32; u32; -1.0, 0.0, 1.0, "S0", "D").into_iter(),
+            arg3_send,
+        );
+        let checker = CheckerContext::new(
+            || {
+                token_vec!(f32; u32; 1.0, 1.0, 3.0, "S0", "D")
+                    .into_iter()
+                    .map(|a| a.exp())
+            },
+            pcu_out_recv,
+        );
+        parent.add_child(gen1);
+        parent.add_child(gen2);
+        parent.add_child(gen3);
+        parent.add_child(alu);
+        parent.add_child(checker);
+        let executed = parent
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+    }
+
+    #[test]
+    fn block_alu_test() {
+        type BlockToken = Token<Tensor<'static, f32>, u32>;
+        let block = |vals: [f32; 4]| {
+            Token::Val(Tensor::from(
+                ndarray::ArrayD::from_shape_vec(ndarray::IxDyn(&[2, 2]), vals.to_vec()).unwrap(),
+            ))
+        };
+
+        let mut parent = ProgramBuilder::default();
+        let (arg1_send, arg1_recv) = parent.unbounded::<BlockToken>();
+        let (arg2_send, arg2_recv) = parent.unbounded::<BlockToken>();
+        let (arg3_send, arg3_recv) = parent.unbounded::<BlockToken>();
+        let (pcu_out_send, pcu_out_recv) = parent.unbounded::<BlockToken>();
+
+        // a * b + c, where the product of 2D blocks is a matrix product
+        let alu = make_chained_alu(
+            vec![arg1_recv, arg2_recv, arg3_recv],
+            pcu_out_send,
+            vec![
+                ChainedStage {
+                    op: ChainedOp::Binary(ALUMulOp()),
+                    operands: vec![0, 1],
+                },
+                ChainedStage {
+                    op: ChainedOp::Binary(ALUAddOp()),
+                    operands: vec![3, 2],
+                },
+            ],
+        );
+        let gen1 = GeneratorContext::new(
+            move || vec![block([1.0, 2.0, 3.0, 4.0]), Token::Stop(0), Token::Done].into_iter(),
+            arg1_send,
+        );
+        let gen2 = GeneratorContext::new(
+            move || vec![block([0.0, 1.0, 1.0, 0.0]), Token::Stop(0), Token::Done].into_iter(),
+            arg2_send,
+        );
+        let gen3 = GeneratorContext::new(
+            move || vec![block([1.0, 1.0, 1.0, 1.0]), Token::Stop(0), Token::Done].into_iter(),
+            arg3_send,
+        );
+        let checker = CheckerContext::new(
+            move || vec![block([3.0, 2.0, 5.0, 4.0]), Token::Stop(0), Token::Done].into_iter(),
+            pcu_out_recv,
+        );
+        parent.add_child(gen1);
+        parent.add_child(gen2);
+        parent.add_child(gen3);
+        parent.add_child(alu);
+        parent.add_child(checker);
+        let executed = parent
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+    }
+
+    #[test]
+    fn chained_alu_operands_test() {
+        let mut parent = ProgramBuilder::default();
+        let (arg1_send, arg1_recv) = parent.unbounded::<Token<f32, u32>>();
+        let (arg2_send, arg2_recv) = parent.unbounded::<Token<f32, u32>>();
+        let (arg3_send, arg3_recv) = parent.unbounded::<Token<f32, u32>>();
+        let (pcu_out_send, pcu_out_recv) = parent.unbounded::<Token<f32, u32>>();
+
+        // c - a * b, which a left fold can't express
+        let alu = make_chained_alu(
+            vec![arg1_recv, arg2_recv, arg3_recv],
+            pcu_out_send,
+            vec![
+                ChainedStage {
+                    op: ChainedOp::Binary(ALUMulOp()),
+                    operands: vec![0, 1],
+                },
+                ChainedStage {
+                    op: ChainedOp::Binary(ALUSubOp()),
+                    operands: vec![2, 3],
+                },
+            ],
+        );
+        let gen1 = GeneratorContext::new(
+            || token_vec!(f32; u32; 1.0, 2.0, 0.5, "S0", "D").into_iter(),
+            arg1_send,
+        );
+        let gen2 = GeneratorContext::new(
+            || token_vec!(f32; u32; 2.0, 0.5, 4.0, "S0", "D").into_iter(),
+            arg2_send,
+        );
+        let gen3 = GeneratorContext::new(
+            || token_vec!(f32; u32; -1.0, 0.0, 1.0, "S0", "D").into_iter(),
+            arg3_send,
+        );
+        let checker = CheckerContext::new(
+            || token_vec!(f32; u32; -3.0, -1.0, -1.0, "S0", "D").into_iter(),
+            pcu_out_recv,
+        );
+        parent.add_child(gen1);
+        parent.add_child(gen2);
+        parent.add_child(gen3);
+        parent.add_child(alu);
+        parent.add_child(checker);
+        let executed = parent
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+    }
+
+    #[test]
+    fn check_chain_test() {
+        assert_eq!(check_chain(3, &[(2, vec![0, 1]), (2, vec![2, 3])]), Ok(()));
+        // Wrong operand count, a later stage's result, an unread input and an unread result
+        assert!(check_chain(2, &[(2, vec![0])]).is_err());
+        assert!(check_chain(2, &[(2, vec![0, 2]), (1, vec![2])]).is_err());
+        assert!(check_chain(3, &[(2, vec![0, 1])]).is_err());
+        assert!(check_chain(2, &[(2, vec![0, 1]), (1, vec![0])]).is_err());
+    }
 }
@@ -0,0 +1,307 @@
+use dam::{context_tools::*, dam_macros::context_macro};
+
+use super::primitive::Token;
+
+/// Coordinate arithmetic against a constant, used for index remapping (offsets, strides, tiling).
+/// Coordinates are unsigned, so subtracting past zero is an error rather than a wraparound.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CrdScalarOp<CrdType> {
+    Add(CrdType),
+    /// Panics on a coordinate smaller than the offset; the graph builder only checks the offset itself,
+    /// since the coordinates aren't known until the simulation runs.
+    Sub(CrdType),
+    Mul(CrdType),
+    Mod(CrdType),
+    Div(CrdType),
+}
+
+impl<CrdType> CrdScalarOp<CrdType>
+where
+    CrdType: Clone
+        + std::fmt::Debug
+        + std::ops::Add<CrdType, Output = CrdType>
+        + num::CheckedSub
+        + std::ops::Mul<CrdType, Output = CrdType>
+        + std::ops::Rem<CrdType, Output = CrdType>
+        + std::ops::Div<CrdType, Output = CrdType>,
+{
+    /// # Panics
+    /// If `Sub` would take the coordinate below zero.
+    pub fn apply(&self, crd: CrdType) -> CrdType {
+        match self {
+            CrdScalarOp::Add(offset) => crd + offset.clone(),
+            CrdScalarOp::Sub(offset) => crd
+                .checked_sub(offset)
+                .unwrap_or_else(|| panic!("Coordinate {crd:?} is less than the offset {offset:?}")),
+            CrdScalarOp::Mul(stride) => crd * stride.clone(),
+            CrdScalarOp::Mod(modulus) => crd % modulus.clone(),
+            CrdScalarOp::Div(divisor) => crd / divisor.clone(),
+        }
+    }
+}
+
+/// Elementwise combination of two aligned coordinate streams.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CrdBinaryOp {
+    Min,
+    Max,
+}
+
+impl CrdBinaryOp {
+    pub fn apply<CrdType: PartialOrd>(&self, crd1: CrdType, crd2: CrdType) -> CrdType {
+        match (self, crd1 < crd2) {
+            (CrdBinaryOp::Min, true) | (CrdBinaryOp::Max, false) => crd1,
+            (CrdBinaryOp::Min, false) | (CrdBinaryOp::Max, true) => crd2,
+        }
+    }
+}
+
+#[context_macro]
+pub struct CrdScalarAlu<CrdType: Clone, StopType: Clone> {
+    pub in_crd: Receiver<Token<CrdType, StopType>>,
+    pub out_crd: Sender<Token<CrdType, StopType>>,
+    pub op: CrdScalarOp<CrdType>,
+}
+
+impl<CrdType: DAMType, StopType: DAMType> CrdScalarAlu<CrdType, StopType>
+where
+    CrdScalarAlu<CrdType, StopType>: Context,
+{
+    pub fn new(
+        in_crd: Receiver<Token<CrdType, StopType>>,
+        out_crd: Sender<Token<CrdType, StopType>>,
+        op: CrdScalarOp<CrdType>,
+    ) -> Self {
+        let alu = CrdScalarAlu {
+            in_crd,
+            out_crd,
+            op,
+            context_info: Default::default(),
+        };
+        (alu).in_crd.attach_receiver(&alu);
+        (alu).out_crd.attach_sender(&alu);
+
+        alu
+    }
+}
+
+impl<CrdType, StopType> Context for CrdScalarAlu<CrdType, StopType>
+where
+    CrdType: DAMType
+        + std::ops::Add<CrdType, Output = CrdType>
+        + num::CheckedSub
+        + std::ops::Mul<CrdType, Output = CrdType>
+        + std::ops::Rem<CrdType, Output = CrdType>
+        + std::ops::Div<CrdType, Output = CrdType>,
+    StopType: DAMType,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) {
+        loop {
+            match self.in_crd.dequeue(&self.time) {
+                Ok(curr_in) => {
+                    let output = match curr_in.data {
+                        Token::Val(crd) => Token::Val(self.op.apply(crd)),
+                        tkn => tkn,
+                    };
+                    let channel_elem = ChannelElement::new(self.time.tick() + 1, output.clone());
+                    self.out_crd.enqueue(&self.time, channel_elem).unwrap();
+                    if let Token::Done = output {
+                        return;
+                    }
+                }
+                Err(_) => {
+                    panic!("Unexpected end of stream");
+                }
+            }
+            self.time.incr_cycles(1);
+        }
+    }
+}
+
+pub struct CrdBinaryAluData<CrdType: Clone, StopType: Clone> {
+    pub in_crd1: Receiver<Token<CrdType, StopType>>,
+    pub in_crd2: Receiver<Token<CrdType, StopType>>,
+    pub out_crd: Sender<Token<CrdType, StopType>>,
+}
+
+#[context_macro]
+pub struct CrdBinaryAlu<CrdType: Clone, StopType: Clone> {
+    alu_data: CrdBinaryAluData<CrdType, StopType>,
+    op: CrdBinaryOp,
+}
+
+impl<CrdType: DAMType, StopType: DAMType> CrdBinaryAlu<CrdType, StopType>
+where
+    CrdBinaryAlu<CrdType, StopType>: Context,
+{
+    pub fn new(alu_data: CrdBinaryAluData<CrdType, StopType>, op: CrdBinaryOp) -> Self {
+        let alu = CrdBinaryAlu {
+            alu_data,
+            op,
+            context_info: Default::default(),
+        };
+        (alu.alu_data.in_crd1).attach_receiver(&alu);
+        (alu.alu_data.in_crd2).attach_receiver(&alu);
+        (alu.alu_data.out_crd).attach_sender(&alu);
+
+        alu
+    }
+}
+
+impl<CrdType, StopType> Context for CrdBinaryAlu<CrdType, StopType>
+where
+    CrdType: DAMType + std::cmp::PartialOrd,
+    StopType: DAMType + std::cmp::PartialEq,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) {
+        loop {
+            let crd1_deq = self.alu_data.in_crd1.dequeue(&self.time);
+            let crd2_deq = self.alu_data.in_crd2.dequeue(&self.time);
+            match (crd1_deq, crd2_deq) {
+                (Ok(crd1), Ok(crd2)) => {
+                    let output = match (crd1.data, crd2.data) {
+                        (Token::Val(crd1), Token::Val(crd2)) => {
+                            Token::Val(self.op.apply(crd1, crd2))
+                        }
+                        // An empty operand acts as the identity, matching the value ALUs
+                        (Token::Val(crd), Token::Empty) | (Token::Empty, Token::Val(crd)) => {
+                            Token::Val(crd)
+                        }
+                        (Token::Stop(stkn1), Token::Stop(stkn2)) => {
+                            assert_eq!(stkn1, stkn2, "Stop tokens must be the same");
+                            Token::Stop(stkn1)
+                        }
+                        (Token::Empty, Token::Empty) => Token::Empty,
+                        (Token::Done, Token::Done) => Token::Done,
+                        (tkn1, tkn2) => {
+                            panic!("Misaligned coordinate streams: {:?} and {:?}", tkn1, tkn2);
+                        }
+                    };
+                    let channel_elem = ChannelElement::new(self.time.tick() + 1, output.clone());
+                    self.alu_data
+                        .out_crd
+                        .enqueue(&self.time, channel_elem)
+                        .unwrap();
+                    if let Token::Done = output {
+                        return;
+                    }
+                }
+                (_, _) => {
+                    panic!("Unexpected end of stream");
+                }
+            }
+            self.time.incr_cycles(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dam::{
+        simulation::{InitializationOptions, ProgramBuilder, RunOptions},
+        utility_contexts::{CheckerContext, GeneratorContext},
+    };
+
+    use crate::{templates::primitive::Token, token_vec};
+
+    use super::{CrdBinaryAlu, CrdBinaryAluData, CrdBinaryOp, CrdScalarAlu, CrdScalarOp};
+
+    #[test]
+    fn crd_offset_test() {
+        let in_crd = || token_vec!(u32; u32; 0, 1, 3, "S0", "N", "S0", 2, "S1", "D").into_iter();
+        let out_crd = || token_vec!(u32; u32; 4, 5, 7, "S0", "N", "S0", 6, "S1", "D").into_iter();
+        crd_scalar_test(in_crd, out_crd, CrdScalarOp::Add(4));
+        crd_scalar_test(out_crd, in_crd, CrdScalarOp::Sub(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "Coordinate 3 is less than the offset 4")]
+    fn crd_offset_underflow_test() {
+        CrdScalarOp::Sub(4u32).apply(3);
+    }
+
+    #[test]
+    fn crd_stride_mod_test() {
+        let in_crd = || token_vec!(u32; u32; 0, 1, 3, "S0", 5, 7, "S1", "D").into_iter();
+        let out_crd = || token_vec!(u32; u32; 0, 2, 6, "S0", 10, 14, "S1", "D").into_iter();
+        crd_scalar_test(in_crd, out_crd, CrdScalarOp::Mul(2));
+
+        let out_crd = || token_vec!(u32; u32; 0, 1, 0, "S0", 2, 1, "S1", "D").into_iter();
+        crd_scalar_test(in_crd, out_crd, CrdScalarOp::Mod(3));
+
+        let out_crd = || token_vec!(u32; u32; 0, 0, 1, "S0", 1, 2, "S1", "D").into_iter();
+        crd_scalar_test(in_crd, out_crd, CrdScalarOp::Div(3));
+    }
+
+    #[test]
+    fn crd_min_max_test() {
+        let in_crd1 = || token_vec!(u32; u32; 0, 4, 3, "S0", "N", "S1", "D").into_iter();
+        let in_crd2 = || token_vec!(u32; u32; 2, 1, 3, "S0", 6, "S1", "D").into_iter();
+        let out_crd = || token_vec!(u32; u32; 0, 1, 3, "S0", 6, "S1", "D").into_iter();
+        crd_binary_test(in_crd1, in_crd2, out_crd, CrdBinaryOp::Min);
+
+        let out_crd = || token_vec!(u32; u32; 2, 4, 3, "S0", 6, "S1", "D").into_iter();
+        crd_binary_test(in_crd1, in_crd2, out_crd, CrdBinaryOp::Max);
+    }
+
+    fn crd_scalar_test<IRT, ORT>(in_crd: fn() -> IRT, out_crd: fn() -> ORT, op: CrdScalarOp<u32>)
+    where
+        IRT: Iterator<Item = Token<u32, u32>> + 'static,
+        ORT: Iterator<Item = Token<u32, u32>> + 'static,
+    {
+        let mut parent = ProgramBuilder::default();
+        let (in_crd_sender, in_crd_receiver) = parent.unbounded::<Token<u32, u32>>();
+        let (out_crd_sender, out_crd_receiver) = parent.unbounded::<Token<u32, u32>>();
+
+        let alu = CrdScalarAlu::new(in_crd_receiver, out_crd_sender, op);
+        let gen1 = GeneratorContext::new(in_crd, in_crd_sender);
+        let crd_checker = CheckerContext::new(out_crd, out_crd_receiver);
+        parent.add_child(gen1);
+        parent.add_child(crd_checker);
+        parent.add_child(alu);
+        let executed = parent
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+    }
+
+    fn crd_binary_test<IRT1, IRT2, ORT>(
+        in_crd1: fn() -> IRT1,
+        in_crd2: fn() -> IRT2,
+        out_crd: fn() -> ORT,
+        op: CrdBinaryOp,
+    ) where
+        IRT1: Iterator<Item = Token<u32, u32>> + 'static,
+        IRT2: Iterator<Item = Token<u32, u32>> + 'static,
+        ORT: Iterator<Item = Token<u32, u32>> + 'static,
+    {
+        let mut parent = ProgramBuilder::default();
+        let (in_crd1_sender, in_crd1_receiver) = parent.unbounded::<Token<u32, u32>>();
+        let (in_crd2_sender, in_crd2_receiver) = parent.unbounded::<Token<u32, u32>>();
+        let (out_crd_sender, out_crd_receiver) = parent.unbounded::<Token<u32, u32>>();
+
+        let data = CrdBinaryAluData::<u32, u32> {
+            in_crd1: in_crd1_receiver,
+            in_crd2: in_crd2_receiver,
+            out_crd: out_crd_sender,
+        };
+        let alu = CrdBinaryAlu::new(data, op);
+        let gen1 = GeneratorContext::new(in_crd1, in_crd1_sender);
+        let gen2 = GeneratorContext::new(in_crd2, in_crd2_sender);
+        let crd_checker = CheckerContext::new(out_crd, out_crd_receiver);
+        parent.add_child(gen1);
+        parent.add_child(gen2);
+        parent.add_child(crd_checker);
+        parent.add_child(alu);
+        let executed = parent
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+    }
+}
@@ -1,7 +1,12 @@
+use std::ops::Sub;
+
 use dam::{context_tools::*, dam_macros::context_macro};
 
 use super::primitive::Token;
 
+type MaskFn<ValType, StopType> =
+    Box<dyn Fn(Token<ValType, StopType>, Token<ValType, StopType>) -> bool + Send + Sync>;
+
 pub struct CrdMaskData<ValType: Clone, StopType: Clone> {
     pub in_crd_inner: Receiver<Token<ValType, StopType>>,
     pub in_crd_outer: Receiver<Token<ValType, StopType>>,
@@ -14,7 +19,64 @@ pub struct CrdMaskData<ValType: Clone, StopType: Clone> {
 #[context_macro]
 pub struct CrdMask<ValType: Clone, StopType: Clone> {
     crd_mask_data: CrdMaskData<ValType, StopType>,
-    predicate: fn(Token<ValType, StopType>, Token<ValType, StopType>) -> bool,
+    predicate: MaskFn<ValType, StopType>,
+}
+
+/// Library of named mask predicates for [CrdMask].
+/// A predicate is evaluated on (outer, inner) coordinates and returns true when the inner coordinate is dropped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MaskPredicate<CrdType> {
+    /// Keeps the upper triangle (inner >= outer)
+    Upper,
+    /// Keeps the lower triangle (inner <= outer), also used for causal attention
+    Lower,
+    /// Keeps only the diagonal (inner == outer)
+    Diagonal,
+    /// Keeps coordinates within the band |outer - inner| <= width
+    Band(CrdType),
+    /// Keeps inner coordinates in the half-open range [lo, hi)
+    Range(CrdType, CrdType),
+}
+
+impl<CrdType> MaskPredicate<CrdType>
+where
+    CrdType: Clone + PartialOrd + Sub<CrdType, Output = CrdType>,
+{
+    /// Looks up a predicate by the name used in the graph, taking band width / range bounds from params.
+    pub fn from_name(name: &str, params: &[CrdType]) -> Option<Self> {
+        match (name, params) {
+            ("upper" | "triu", []) => Some(Self::Upper),
+            ("lower" | "tril" | "causal", []) => Some(Self::Lower),
+            ("diag" | "diagonal", []) => Some(Self::Diagonal),
+            ("band", [width]) => Some(Self::Band(width.clone())),
+            ("range", [lo, hi]) => Some(Self::Range(lo.clone(), hi.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn is_masked<StopType>(
+        &self,
+        outer: &Token<CrdType, StopType>,
+        inner: &Token<CrdType, StopType>,
+    ) -> bool {
+        let (Token::Val(outer), Token::Val(inner)) = (outer, inner) else {
+            return false;
+        };
+        match self {
+            MaskPredicate::Upper => inner < outer,
+            MaskPredicate::Lower => inner > outer,
+            MaskPredicate::Diagonal => inner != outer,
+            MaskPredicate::Band(width) => {
+                let dist = if inner > outer {
+                    inner.clone() - outer.clone()
+                } else {
+                    outer.clone() - inner.clone()
+                };
+                dist > *width
+            }
+            MaskPredicate::Range(lo, hi) => inner < lo || inner >= hi,
+        }
+    }
 }
 
 impl<ValType: DAMType, StopType: DAMType> CrdMask<ValType, StopType>
@@ -23,11 +85,14 @@ where
 {
     pub fn new(
         crd_mask_data: CrdMaskData<ValType, StopType>,
-        predicate: fn(Token<ValType, StopType>, Token<ValType, StopType>) -> bool,
+        predicate: impl Fn(Token<ValType, StopType>, Token<ValType, StopType>) -> bool
+            + Send
+            + Sync
+            + 'static,
     ) -> Self {
         let mask = CrdMask {
             crd_mask_data,
-            predicate,
+            predicate: Box::new(predicate),
             context_info: Default::default(),
         };
         (mask.crd_mask_data.in_crd_inner).attach_receiver(&mask);
@@ -158,7 +223,7 @@ mod tests {
 
     use crate::{templates::primitive::Token, token_vec};
 
-    use super::{CrdMask, CrdMaskData};
+    use super::{CrdMask, CrdMaskData, MaskPredicate};
 
     #[test]
     fn test_tril_mask() {
@@ -180,6 +245,7 @@ mod tests {
             out_crd_outer,
             out_crd_inner,
             out_ref_inner,
+            |x, y| x > y,
         );
     }
 
@@ -201,9 +267,81 @@ mod tests {
             out_crd_outer,
             out_crd_inner,
             out_ref_inner,
+            |x, y| x > y,
         );
     }
 
+    #[test]
+    fn test_causal_mask() {
+        let in_crd_outer = || token_vec!(u32; u32; 0, 1, 2, "S0", "D").into_iter();
+        let in_crd_inner =
+            || token_vec!(u32; u32; 0, 1, 2, "S0", 0, 1, 2, "S0", 0, 1, 2, "S1", "D").into_iter();
+        let in_ref_inner =
+            || token_vec!(u32; u32; 0, 1, 2, "S0", 3, 4, 5, "S0", 6, 7, 8, "S1", "D").into_iter();
+
+        let out_crd_outer = || token_vec!(u32; u32; 0, 1, 2, "S0", "D").into_iter();
+        let out_crd_inner =
+            || token_vec!(u32; u32; 0, "S0", 0, 1, "S0", 0, 1, 2, "S1", "D").into_iter();
+        let out_ref_inner =
+            || token_vec!(u32; u32; 0, "S0", 3, 4, "S0", 6, 7, 8, "S1", "D").into_iter();
+        let predicate = MaskPredicate::<u32>::from_name("causal", &[]).unwrap();
+        mask_test(
+            in_crd_outer,
+            in_crd_inner,
+            in_ref_inner,
+            out_crd_outer,
+            out_crd_inner,
+            out_ref_inner,
+            move |x, y| predicate.is_masked(&x, &y),
+        );
+    }
+
+    #[test]
+    fn test_band_mask() {
+        let in_crd_outer = || token_vec!(u32; u32; 0, 1, 3, "S0", "D").into_iter();
+        let in_crd_inner = || {
+            token_vec!(u32; u32; 0, 1, 2, 3, "S0", 0, 1, 2, 3, "S0", 0, 1, 2, 3, "S1", "D")
+                .into_iter()
+        };
+        let in_ref_inner = || {
+            token_vec!(u32; u32; 0, 1, 2, 3, "S0", 4, 5, 6, 7, "S0", 8, 9, 10, 11, "S1", "D")
+                .into_iter()
+        };
+
+        let out_crd_outer = || token_vec!(u32; u32; 0, 1, 3, "S0", "D").into_iter();
+        let out_crd_inner =
+            || token_vec!(u32; u32; 0, 1, "S0", 0, 1, 2, "S0", 2, 3, "S1", "D").into_iter();
+        let out_ref_inner =
+            || token_vec!(u32; u32; 0, 1, "S0", 4, 5, 6, "S0", 10, 11, "S1", "D").into_iter();
+        let predicate = MaskPredicate::from_name("band", &[1u32]).unwrap();
+        mask_test(
+            in_crd_outer,
+            in_crd_inner,
+            in_ref_inner,
+            out_crd_outer,
+            out_crd_inner,
+            out_ref_inner,
+            move |x, y| predicate.is_masked(&x, &y),
+        );
+    }
+
+    #[test]
+    fn test_mask_predicates() {
+        let diag = MaskPredicate::<u32>::Diagonal;
+        assert!(!diag.is_masked::<u32>(&Token::Val(2), &Token::Val(2)));
+        assert!(diag.is_masked::<u32>(&Token::Val(2), &Token::Val(3)));
+
+        let range = MaskPredicate::from_name("range", &[2u32, 4]).unwrap();
+        assert!(range.is_masked::<u32>(&Token::Val(0), &Token::Val(1)));
+        assert!(!range.is_masked::<u32>(&Token::Val(0), &Token::Val(2)));
+        assert!(range.is_masked::<u32>(&Token::Val(0), &Token::Val(4)));
+
+        // Stop tokens are never masked
+        assert!(!diag.is_masked::<u32>(&Token::Stop(0), &Token::Val(3)));
+        assert_eq!(MaskPredicate::<u32>::from_name("band", &[]), None);
+        assert_eq!(MaskPredicate::<u32>::from_name("unknown", &[]), None);
+    }
+
     fn mask_test<IRT1, IRT2, IRT3, ORT1, ORT2, ORT3>(
         in_crd_outer: fn() -> IRT2,
         in_crd_inner: fn() -> IRT1,
@@ -211,6 +349,7 @@ mod tests {
         out_crd_outer: fn() -> ORT2,
         out_crd_inner: fn() -> ORT1,
         out_ref_inner: fn() -> ORT3,
+        predicate: impl Fn(Token<u32, u32>, Token<u32, u32>) -> bool + Send + Sync + 'static,
     ) where
         IRT1: Iterator<Item = Token<u32, u32>> + 'static,
         IRT2: Iterator<Item = Token<u32, u32>> + 'static,
@@ -243,7 +382,7 @@ mod tests {
             out_crd_outer: mask_out_ocrd_sender,
             out_ref_inner: mask_out_ref_sender,
         };
-        let mask = CrdMask::new(mask_data, |x, y| x > y);
+        let mask = CrdMask::new(mask_data, predicate);
         parent.add_child(gen1);
         parent.add_child(gen2);
         parent.add_child(gen3);
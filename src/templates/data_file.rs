@@ -0,0 +1,356 @@
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use dam::types::DAMType;
+use num::NumCast;
+use serde::Deserialize;
+
+/// Binary data files start with this, then the dtype code, padding up to 8 bytes and the
+/// number of elements as a little-endian u64. The elements follow, little-endian and unpadded.
+const MAGIC: &[u8; 8] = b"COMALDAT";
+const HEADER_LEN: usize = 24;
+
+/// Element type stored in a binary data file
+#[derive(ValueEnum, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DType {
+    U32,
+    U64,
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl DType {
+    const ALL: [DType; 6] = [
+        DType::U32,
+        DType::U64,
+        DType::I32,
+        DType::I64,
+        DType::F32,
+        DType::F64,
+    ];
+
+    fn code(self) -> u8 {
+        Self::ALL.iter().position(|dtype| *dtype == self).unwrap() as u8
+    }
+
+    fn size(self) -> usize {
+        match self {
+            DType::U32 | DType::I32 | DType::F32 => 4,
+            DType::U64 | DType::I64 | DType::F64 => 8,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        matches!(self, DType::F32 | DType::F64)
+    }
+}
+
+impl Display for DType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DType::U32 => write!(f, "u32"),
+            DType::U64 => write!(f, "u64"),
+            DType::I32 => write!(f, "i32"),
+            DType::I64 => write!(f, "i64"),
+            DType::F32 => write!(f, "f32"),
+            DType::F64 => write!(f, "f64"),
+        }
+    }
+}
+
+/// Types seg/crd/vals files can be loaded as.
+pub trait DataElement: DAMType + FromStr + NumCast + Copy {
+    const DTYPE: DType;
+
+    fn write_le(self, out: &mut impl Write) -> std::io::Result<()>;
+}
+
+macro_rules! data_element_impl {
+    ($(($elem: ty, $dtype: ident)),*) => {
+        $(
+            impl DataElement for $elem {
+                const DTYPE: DType = DType::$dtype;
+
+                fn write_le(self, out: &mut impl Write) -> std::io::Result<()> {
+                    out.write_all(&self.to_le_bytes())
+                }
+            }
+        )*
+    };
+}
+
+data_element_impl!(
+    (u32, U32),
+    (u64, U64),
+    (i32, I32),
+    (i64, I64),
+    (f32, F32),
+    (f64, F64)
+);
+
+#[derive(Debug)]
+pub enum DataFileError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        text: String,
+    },
+    Header {
+        path: PathBuf,
+        reason: String,
+    },
+    DTypeMismatch {
+        path: PathBuf,
+        stored: DType,
+        requested: DType,
+    },
+    OutOfRange {
+        path: PathBuf,
+        index: usize,
+        requested: DType,
+    },
+    /// The text file was modified after its binary version was written
+    StaleBinary {
+        path: PathBuf,
+    },
+}
+
+impl Display for DataFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataFileError::Io { path, source } => write!(f, "{path:?}: {source}"),
+            DataFileError::Parse { path, line, text } => {
+                write!(f, "{path:?} line {line}: can't parse {text:?}")
+            }
+            DataFileError::Header { path, reason } => write!(f, "{path:?}: {reason}"),
+            DataFileError::DTypeMismatch {
+                path,
+                stored,
+                requested,
+            } => write!(
+                f,
+                "{path:?} holds {stored} values, which can't be read as {requested}"
+            ),
+            DataFileError::OutOfRange {
+                path,
+                index,
+                requested,
+            } => write!(f, "{path:?} element {index} doesn't fit in {requested}"),
+            DataFileError::StaleBinary { path } => write!(
+                f,
+                "{path:?} is newer than {:?}, regenerate or delete the binary file",
+                binary_path(path)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DataFileError {}
+
+/// Where the binary version of a text data file lives: the same name with `.bin` appended.
+pub fn binary_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bin");
+    PathBuf::from(name)
+}
+
+/// Reads one value per line. Blank lines are skipped, anything else that doesn't parse is an error.
+pub fn read_text<T: FromStr>(path: &Path) -> Result<Vec<T>, DataFileError> {
+    let io_err = |source| DataFileError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let reader = BufReader::new(File::open(path).map_err(io_err)?);
+    let mut vals = vec![];
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(io_err)?;
+        let text = line.trim();
+        if text.is_empty() {
+            continue;
+        }
+        vals.push(text.parse().map_err(|_| DataFileError::Parse {
+            path: path.to_path_buf(),
+            line: index + 1,
+            text: text.to_string(),
+        })?);
+    }
+    Ok(vals)
+}
+
+fn decode<S: num::ToPrimitive, T: DataElement>(
+    path: &Path,
+    body: &[u8],
+    from_le: fn(&[u8]) -> S,
+) -> Result<Vec<T>, DataFileError> {
+    (body.chunks_exact(std::mem::size_of::<S>()).enumerate())
+        .map(|(index, bytes)| {
+            <T as NumCast>::from(from_le(bytes)).ok_or_else(|| DataFileError::OutOfRange {
+                path: path.to_path_buf(),
+                index,
+                requested: T::DTYPE,
+            })
+        })
+        .collect()
+}
+
+/// Reads a binary data file. Integers convert to any type that can hold them, but floats only load as floats.
+pub fn read_binary<T: DataElement>(path: &Path) -> Result<Vec<T>, DataFileError> {
+    let bytes = fs::read(path).map_err(|source| DataFileError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let header_err = |reason: String| DataFileError::Header {
+        path: path.to_path_buf(),
+        reason,
+    };
+    if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(header_err("not a binary data file".to_string()));
+    }
+    let dtype = *DType::ALL
+        .get(bytes[MAGIC.len()] as usize)
+        .ok_or_else(|| header_err(format!("unknown dtype code {}", bytes[MAGIC.len()])))?;
+    let len = u64::from_le_bytes(bytes[MAGIC.len() + 8..HEADER_LEN].try_into().unwrap()) as usize;
+    let body = &bytes[HEADER_LEN..];
+    if len.checked_mul(dtype.size()) != Some(body.len()) {
+        return Err(header_err(format!(
+            "header says {len} {dtype} values, but the file holds {} bytes of data",
+            body.len()
+        )));
+    }
+    if dtype.is_float() && !T::DTYPE.is_float() {
+        return Err(DataFileError::DTypeMismatch {
+            path: path.to_path_buf(),
+            stored: dtype,
+            requested: T::DTYPE,
+        });
+    }
+
+    match dtype {
+        DType::U32 => decode(path, body, |b| u32::from_le_bytes(b.try_into().unwrap())),
+        DType::U64 => decode(path, body, |b| u64::from_le_bytes(b.try_into().unwrap())),
+        DType::I32 => decode(path, body, |b| i32::from_le_bytes(b.try_into().unwrap())),
+        DType::I64 => decode(path, body, |b| i64::from_le_bytes(b.try_into().unwrap())),
+        DType::F32 => decode(path, body, |b| f32::from_le_bytes(b.try_into().unwrap())),
+        DType::F64 => decode(path, body, |b| f64::from_le_bytes(b.try_into().unwrap())),
+    }
+}
+
+pub fn write_binary<T: DataElement>(path: &Path, vals: &[T]) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[T::DTYPE.code(), 0, 0, 0, 0, 0, 0, 0])?;
+    writer.write_all(&(vals.len() as u64).to_le_bytes())?;
+    for val in vals {
+        val.write_le(&mut writer)?;
+    }
+    writer.flush()
+}
+
+/// Loads a data file, preferring its binary version when there is one.
+/// A text file edited after its binary version was written is an error rather than silently ignored.
+pub fn read_data<T: DataElement>(path: &Path) -> Result<Vec<T>, DataFileError> {
+    let binary = binary_path(path);
+    if !binary.exists() {
+        return read_text(path);
+    }
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    if let (Some(text_time), Some(binary_time)) = (modified(path), modified(&binary)) {
+        if text_time > binary_time {
+            return Err(DataFileError::StaleBinary {
+                path: path.to_path_buf(),
+            });
+        }
+    }
+    read_binary(&binary)
+}
+
+/// Whether `read_data` would find either version of the file.
+pub fn data_exists(path: &Path) -> bool {
+    path.exists() || binary_path(path).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::time::{Duration, SystemTime};
+
+    use super::{binary_path, read_binary, read_data, write_binary, DataFileError};
+    use crate::utils::scratch::ScratchDir;
+
+    #[test]
+    fn binary_round_trip_test() {
+        let dir = ScratchDir::new("binary_round_trip");
+        let path = dir.write("round_trip", "1\n2\n");
+        write_binary(&binary_path(&path), &[0u32, 3, 7]).unwrap();
+
+        // The binary file wins over the text one, and u32s widen to u64
+        assert_eq!(read_data::<u32>(&path).unwrap(), vec![0, 3, 7]);
+        assert_eq!(read_data::<u64>(&path).unwrap(), vec![0, 3, 7]);
+        assert_eq!(
+            read_binary::<i32>(&binary_path(&path)).unwrap(),
+            vec![0, 3, 7]
+        );
+
+        write_binary(&binary_path(&path), &[1.5f32, -2.0]).unwrap();
+        assert_eq!(read_data::<f64>(&path).unwrap(), vec![1.5, -2.0]);
+        assert!(matches!(
+            read_data::<u32>(&path),
+            Err(DataFileError::DTypeMismatch { .. })
+        ));
+
+        write_binary(&binary_path(&path), &[u64::MAX]).unwrap();
+        assert!(matches!(
+            read_data::<u32>(&path),
+            Err(DataFileError::OutOfRange { index: 0, .. })
+        ));
+
+        // Truncated data doesn't match the length in the header
+        let bytes = fs::read(binary_path(&path)).unwrap();
+        fs::write(binary_path(&path), &bytes[..bytes.len() - 1]).unwrap();
+        assert!(matches!(
+            read_data::<u64>(&path),
+            Err(DataFileError::Header { .. })
+        ));
+    }
+
+    #[test]
+    fn text_parse_error_test() {
+        let dir = ScratchDir::new("text_parse_error");
+        let path = dir.write("parse_error", "0\n2\n\n5\nx4\n");
+        assert!(matches!(
+            read_data::<u32>(&path),
+            Err(DataFileError::Parse { line: 5, text, .. }) if text == "x4"
+        ));
+
+        fs::write(&path, " 0\n2\n\n5\n").unwrap();
+        assert_eq!(read_data::<u32>(&path).unwrap(), vec![0, 2, 5]);
+    }
+
+    #[test]
+    fn stale_binary_test() {
+        let dir = ScratchDir::new("stale_binary");
+        let path = dir.write("stale", "1\n2\n");
+        write_binary(&binary_path(&path), &[0u32, 3, 7]).unwrap();
+        assert_eq!(read_data::<u32>(&path).unwrap(), vec![0, 3, 7]);
+
+        // Editing the text file afterwards leaves the binary one out of date
+        let later = SystemTime::now() + Duration::from_secs(60);
+        let text = File::options().write(true).open(&path).unwrap();
+        text.set_modified(later).unwrap();
+        assert!(matches!(
+            read_data::<u32>(&path),
+            Err(DataFileError::StaleBinary { .. })
+        ));
+    }
+}
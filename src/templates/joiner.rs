@@ -41,7 +41,6 @@ where
 impl<ValType, StopType> Context for Intersect<ValType, StopType>
 where
     ValType: DAMType
-        + std::ops::AddAssign<u32>
         + std::ops::AddAssign<ValType>
         + std::ops::Mul<ValType, Output = ValType>
         + std::ops::Add<ValType, Output = ValType>
@@ -217,7 +216,6 @@ where
 impl<ValType, StopType> Context for Union<ValType, StopType>
 where
     ValType: DAMType
-        + std::ops::AddAssign<u32>
         + std::ops::AddAssign<ValType>
         + std::ops::Mul<ValType, Output = ValType>
         + std::ops::Add<ValType, Output = ValType>
@@ -423,13 +421,252 @@ where
     }
 }
 
+/// Joiner data for an arbitrary number of (crd, ref) input pairs, with one output ref per input.
+pub struct CrdJoinerNData<ValType: Clone, StopType: Clone> {
+    pub in_crds: Vec<Receiver<Token<ValType, StopType>>>,
+    pub in_refs: Vec<Receiver<Token<ValType, StopType>>>,
+    pub out_refs: Vec<Sender<Token<ValType, StopType>>>,
+    pub out_crd: Sender<Token<ValType, StopType>>,
+}
+
+impl<ValType: DAMType, StopType: DAMType> CrdJoinerNData<ValType, StopType> {
+    fn attach<C: Context>(&self, ctx: &C) {
+        assert!(self.in_crds.len() >= 2, "Joiners need at least two inputs");
+        assert_eq!(self.in_crds.len(), self.in_refs.len());
+        assert_eq!(self.in_crds.len(), self.out_refs.len());
+        self.in_crds.iter().for_each(|crd| crd.attach_receiver(ctx));
+        self.in_refs.iter().for_each(|rf| rf.attach_receiver(ctx));
+        self.out_refs.iter().for_each(|rf| rf.attach_sender(ctx));
+        self.out_crd.attach_sender(ctx);
+    }
+}
+
+/// N-way intersection, processing one step of all inputs per cycle like a single hardware joiner.
+#[context_macro]
+pub struct IntersectN<ValType: Clone, StopType: Clone> {
+    intersect_data: CrdJoinerNData<ValType, StopType>,
+}
+
+impl<ValType: DAMType, StopType: DAMType> IntersectN<ValType, StopType>
+where
+    IntersectN<ValType, StopType>: Context,
+{
+    pub fn new(intersect_data: CrdJoinerNData<ValType, StopType>) -> Self {
+        let int = IntersectN {
+            intersect_data,
+            context_info: Default::default(),
+        };
+        int.intersect_data.attach(&int);
+
+        int
+    }
+}
+
+impl<ValType, StopType> Context for IntersectN<ValType, StopType>
+where
+    ValType: DAMType + std::cmp::PartialOrd<ValType>,
+    StopType: DAMType + std::cmp::PartialEq,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) {
+        let data = &self.intersect_data;
+        let time = &self.time;
+        let num_inputs = data.in_crds.len();
+        let peek_refs = || {
+            (data.in_refs.iter())
+                .map(|in_ref| in_ref.peek_next(time).unwrap().data)
+                .collect::<Vec<_>>()
+        };
+        let advance = |idx: usize| {
+            data.in_crds[idx].dequeue(time).unwrap();
+            data.in_refs[idx].dequeue(time).unwrap();
+        };
+        let emit = |crd, refs: Vec<Token<ValType, StopType>>| {
+            let curr_time = time.tick();
+            data.out_crd
+                .enqueue(time, ChannelElement::new(curr_time + 1, crd))
+                .unwrap();
+            data.out_refs.iter().zip(refs).for_each(|(out_ref, rf)| {
+                out_ref
+                    .enqueue(time, ChannelElement::new(curr_time + 1, rf))
+                    .unwrap();
+            });
+        };
+        loop {
+            let crds: Vec<_> = (data.in_crds.iter())
+                .map(|in_crd| in_crd.peek_next(time).unwrap().data)
+                .collect();
+            let max_crd = crds
+                .iter()
+                .filter_map(|crd| match crd {
+                    Token::Val(val) => Some(val.clone()),
+                    _ => None,
+                })
+                .reduce(|acc, val| if val > acc { val } else { acc });
+
+            if crds.iter().any(|crd| matches!(crd, Token::Empty)) {
+                // Like the binary Intersect, an empty fiber is forwarded on every output
+                emit(Token::Empty, vec![Token::Empty; num_inputs]);
+                crds.iter()
+                    .enumerate()
+                    .filter(|(_, crd)| matches!(crd, Token::Empty))
+                    .for_each(|(idx, _)| advance(idx));
+            } else if crds.iter().all(|crd| matches!(crd, Token::Val(_))) {
+                let max_crd = max_crd.unwrap();
+                if crds.iter().all(|crd| *crd == Token::Val(max_crd.clone())) {
+                    emit(Token::Val(max_crd), peek_refs());
+                    (0..num_inputs).for_each(&advance);
+                } else {
+                    // Every input behind the largest coordinate can't match it, so they all skip ahead
+                    crds.iter()
+                        .enumerate()
+                        .filter(|(_, crd)| **crd != Token::Val(max_crd.clone()))
+                        .for_each(|(idx, _)| advance(idx));
+                }
+            } else if crds.iter().all(|crd| matches!(crd, Token::Stop(_))) {
+                let stkn = crds[0].clone();
+                assert!(
+                    crds.iter().all(|crd| *crd == stkn),
+                    "Stop tokens must match"
+                );
+                emit(stkn, peek_refs());
+                (0..num_inputs).for_each(&advance);
+            } else if crds.iter().all(|crd| matches!(crd, Token::Done)) {
+                emit(Token::Done, vec![Token::Done; num_inputs]);
+                return;
+            } else {
+                // Once any fiber is exhausted nothing else in this fiber can intersect
+                let to_skip: Vec<usize> = crds
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, crd)| matches!(crd, Token::Val(_)))
+                    .map(|(idx, _)| idx)
+                    .collect();
+                assert!(!to_skip.is_empty(), "Misaligned joiner inputs: {:?}", crds);
+                to_skip.into_iter().for_each(&advance);
+            }
+            self.time.incr_cycles(1);
+        }
+    }
+}
+
+/// N-way union, emitting an Empty ref for every input that doesn't contain the current coordinate.
+#[context_macro]
+pub struct UnionN<ValType: Clone, StopType: Clone> {
+    union_data: CrdJoinerNData<ValType, StopType>,
+}
+
+impl<ValType: DAMType, StopType: DAMType> UnionN<ValType, StopType>
+where
+    UnionN<ValType, StopType>: Context,
+{
+    pub fn new(union_data: CrdJoinerNData<ValType, StopType>) -> Self {
+        let int = UnionN {
+            union_data,
+            context_info: Default::default(),
+        };
+        int.union_data.attach(&int);
+
+        int
+    }
+}
+
+impl<ValType, StopType> Context for UnionN<ValType, StopType>
+where
+    ValType: DAMType + std::cmp::PartialOrd<ValType>,
+    StopType: DAMType + std::cmp::PartialEq,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) {
+        let data = &self.union_data;
+        let time = &self.time;
+        let num_inputs = data.in_crds.len();
+        let advance = |idx: usize| {
+            data.in_crds[idx].dequeue(time).unwrap();
+            data.in_refs[idx].dequeue(time).unwrap();
+        };
+        let emit = |crd, refs: Vec<Token<ValType, StopType>>| {
+            let curr_time = time.tick();
+            data.out_crd
+                .enqueue(time, ChannelElement::new(curr_time + 1, crd))
+                .unwrap();
+            data.out_refs.iter().zip(refs).for_each(|(out_ref, rf)| {
+                out_ref
+                    .enqueue(time, ChannelElement::new(curr_time + 1, rf))
+                    .unwrap();
+            });
+        };
+        loop {
+            let crds: Vec<_> = (data.in_crds.iter())
+                .map(|in_crd| in_crd.peek_next(time).unwrap().data)
+                .collect();
+            let min_crd = crds
+                .iter()
+                .filter_map(|crd| match crd {
+                    Token::Val(val) => Some(val.clone()),
+                    _ => None,
+                })
+                .reduce(|acc, val| if val < acc { val } else { acc });
+
+            if crds.iter().all(|crd| matches!(crd, Token::Empty)) {
+                // Like the binary Union, a fiber that is empty everywhere stays marked as empty
+                emit(Token::Empty, vec![Token::Empty; num_inputs]);
+                (0..num_inputs).for_each(&advance);
+            } else if crds.iter().any(|crd| matches!(crd, Token::Empty)) {
+                crds.iter()
+                    .enumerate()
+                    .filter(|(_, crd)| matches!(crd, Token::Empty))
+                    .for_each(|(idx, _)| advance(idx));
+            } else if let Some(min_crd) = min_crd {
+                let matched: Vec<bool> = crds
+                    .iter()
+                    .map(|crd| *crd == Token::Val(min_crd.clone()))
+                    .collect();
+                let refs = matched
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, is_match)| match is_match {
+                        true => data.in_refs[idx].peek_next(time).unwrap().data,
+                        false => Token::Empty,
+                    })
+                    .collect();
+                emit(Token::Val(min_crd), refs);
+                matched
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, is_match)| *is_match)
+                    .for_each(|(idx, _)| advance(idx));
+            } else if crds.iter().all(|crd| matches!(crd, Token::Stop(_))) {
+                let stkn = crds[0].clone();
+                assert!(
+                    crds.iter().all(|crd| *crd == stkn),
+                    "Stop tokens must match"
+                );
+                let refs = (data.in_refs.iter())
+                    .map(|in_ref| in_ref.peek_next(time).unwrap().data)
+                    .collect();
+                emit(stkn, refs);
+                (0..num_inputs).for_each(&advance);
+            } else if crds.iter().all(|crd| matches!(crd, Token::Done)) {
+                emit(Token::Done, vec![Token::Done; num_inputs]);
+                return;
+            } else {
+                panic!("Misaligned joiner inputs: {:?}", crds);
+            }
+            self.time.incr_cycles(1);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use dam::{simulation::*, utility_contexts::*};
 
     use crate::{templates::primitive::Token, token_vec};
 
-    use super::{CrdJoinerData, Intersect, Union};
+    use super::{CrdJoinerData, CrdJoinerNData, Intersect, IntersectN, Union, UnionN};
     #[test]
     fn intersect_2d_test() {
         let in_crd1 = || token_vec!(u32; u32; 0, "S0", 0, 1, 2, "S1", "D").into_iter();
@@ -590,4 +827,152 @@ mod tests {
             .run(RunOptions::default());
         dbg!(executed.elapsed_cycles());
     }
+
+    #[test]
+    fn intersect_3way_2d_test() {
+        let in_crds = || {
+            vec![
+                token_vec!(u32; u32; 0, 1, 3, "S0", 0, 2, "S1", "D"),
+                token_vec!(u32; u32; 1, 2, 3, "S0", 1, 2, "S1", "D"),
+                token_vec!(u32; u32; 0, 1, 2, 3, "S0", 2, "S1", "D"),
+            ]
+        };
+        let in_refs = || {
+            vec![
+                token_vec!(u32; u32; 0, 1, 2, "S0", 3, 4, "S1", "D"),
+                token_vec!(u32; u32; 0, 1, 2, "S0", 3, 4, "S1", "D"),
+                token_vec!(u32; u32; 0, 1, 2, 3, "S0", 4, "S1", "D"),
+            ]
+        };
+        let out_crd = token_vec!(u32; u32; 1, 3, "S0", 2, "S1", "D");
+        let out_refs = vec![
+            token_vec!(u32; u32; 1, 2, "S0", 4, "S1", "D"),
+            token_vec!(u32; u32; 0, 2, "S0", 4, "S1", "D"),
+            token_vec!(u32; u32; 1, 3, "S0", 4, "S1", "D"),
+        ];
+        joiner_n_test(true, in_crds(), in_refs(), out_crd, out_refs);
+    }
+
+    #[test]
+    fn intersect_3way_empty_test() {
+        let in_crds = vec![
+            token_vec!(u32; u32; 0, 1, "S0", 0, 2, "S1", "D"),
+            token_vec!(u32; u32; 0, 1, "S0", "N", "S1", "D"),
+            token_vec!(u32; u32; 1, "S0", 0, 2, "S1", "D"),
+        ];
+        let in_refs = vec![
+            token_vec!(u32; u32; 0, 1, "S0", 2, 3, "S1", "D"),
+            token_vec!(u32; u32; 0, 1, "S0", "N", "S1", "D"),
+            token_vec!(u32; u32; 0, "S0", 1, 2, "S1", "D"),
+        ];
+        let out_crd = token_vec!(u32; u32; 1, "S0", "N", "S1", "D");
+        let out_refs = vec![
+            token_vec!(u32; u32; 1, "S0", "N", "S1", "D"),
+            token_vec!(u32; u32; 1, "S0", "N", "S1", "D"),
+            token_vec!(u32; u32; 0, "S0", "N", "S1", "D"),
+        ];
+        joiner_n_test(true, in_crds, in_refs, out_crd, out_refs);
+    }
+
+    #[test]
+    fn union_3way_2d_test() {
+        let in_crds = vec![
+            token_vec!(u32; u32; 0, 2, "S0", "S0", 1, "S1", "D"),
+            token_vec!(u32; u32; 1, 2, "S0", 3, "S0", "S1", "D"),
+            token_vec!(u32; u32; 2, "S0", "S0", 1, "S1", "D"),
+        ];
+        let in_refs = vec![
+            token_vec!(u32; u32; 0, 1, "S0", "S0", 2, "S1", "D"),
+            token_vec!(u32; u32; 0, 1, "S0", 2, "S0", "S1", "D"),
+            token_vec!(u32; u32; 0, "S0", "S0", 1, "S1", "D"),
+        ];
+        let out_crd = token_vec!(u32; u32; 0, 1, 2, "S0", 3, "S0", 1, "S1", "D");
+        let out_refs = vec![
+            token_vec!(u32; u32; 0, "N", 1, "S0", "N", "S0", 2, "S1", "D"),
+            token_vec!(u32; u32; "N", 0, 1, "S0", 2, "S0", "N", "S1", "D"),
+            token_vec!(u32; u32; "N", "N", 0, "S0", "N", "S0", 1, "S1", "D"),
+        ];
+        joiner_n_test(false, in_crds, in_refs, out_crd, out_refs);
+    }
+
+    #[test]
+    fn union_3way_empty_test() {
+        let in_crds = vec![
+            token_vec!(u32; u32; 0, "S0", "N", "S1", "D"),
+            token_vec!(u32; u32; 1, "S0", "N", "S1", "D"),
+            token_vec!(u32; u32; 0, "S0", "N", "S1", "D"),
+        ];
+        let in_refs = vec![
+            token_vec!(u32; u32; 0, "S0", "N", "S1", "D"),
+            token_vec!(u32; u32; 0, "S0", "N", "S1", "D"),
+            token_vec!(u32; u32; 1, "S0", "N", "S1", "D"),
+        ];
+        let out_crd = token_vec!(u32; u32; 0, 1, "S0", "N", "S1", "D");
+        let out_refs = vec![
+            token_vec!(u32; u32; 0, "N", "S0", "N", "S1", "D"),
+            token_vec!(u32; u32; "N", 0, "S0", "N", "S1", "D"),
+            token_vec!(u32; u32; 1, "N", "S0", "N", "S1", "D"),
+        ];
+        joiner_n_test(false, in_crds, in_refs, out_crd, out_refs);
+    }
+
+    fn joiner_n_test(
+        intersect: bool,
+        in_crds: Vec<Vec<Token<u32, u32>>>,
+        in_refs: Vec<Vec<Token<u32, u32>>>,
+        out_crd: Vec<Token<u32, u32>>,
+        out_refs: Vec<Vec<Token<u32, u32>>>,
+    ) {
+        let chan_size = 4;
+        let mut parent = ProgramBuilder::default();
+
+        let mut make_inputs = |streams: Vec<Vec<Token<u32, u32>>>| {
+            streams
+                .into_iter()
+                .map(|stream| {
+                    let (sender, receiver) = parent.bounded::<Token<u32, u32>>(chan_size);
+                    parent.add_child(GeneratorContext::new(
+                        move || stream.clone().into_iter(),
+                        sender,
+                    ));
+                    receiver
+                })
+                .collect::<Vec<_>>()
+        };
+        let in_crds = make_inputs(in_crds);
+        let in_refs = make_inputs(in_refs);
+
+        let mut make_outputs = |streams: Vec<Vec<Token<u32, u32>>>| {
+            streams
+                .into_iter()
+                .map(|stream| {
+                    let (sender, receiver) = parent.bounded::<Token<u32, u32>>(chan_size);
+                    parent.add_child(CheckerContext::new(
+                        move || stream.clone().into_iter(),
+                        receiver,
+                    ));
+                    sender
+                })
+                .collect::<Vec<_>>()
+        };
+        let out_refs = make_outputs(out_refs);
+        let out_crd = make_outputs(vec![out_crd]).pop().unwrap();
+
+        let data = CrdJoinerNData::<u32, u32> {
+            in_crds,
+            in_refs,
+            out_refs,
+            out_crd,
+        };
+        if intersect {
+            parent.add_child(IntersectN::new(data));
+        } else {
+            parent.add_child(UnionN::new(data));
+        }
+        let executed = parent
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+    }
 }
@@ -1,8 +1,10 @@
 pub mod accumulator;
 pub mod alu;
 pub mod array;
+pub mod crd_alu;
 pub mod crd_manager;
 pub mod crd_masker;
+pub mod data_file;
 pub mod joiner;
 pub mod primitive;
 pub mod rd_scanner;
@@ -45,6 +45,84 @@ impl<T: num::Float> Exp for T {
     }
 }
 
+RegisterALUOp!(ALUNegOp, |(i0), ()| [-i0], T: DAMType + std::ops::Neg<Output = T>);
+
+macro_rules! RegisterUnaryOp {
+    ($name: ident, $trait: ident, $method: ident, $float_impl: expr) => {
+        pub trait $trait {
+            fn $method(self) -> Self;
+        }
+
+        RegisterALUOp!($name, |(i0), ()| [i0.$method()], T: DAMType + $trait);
+
+        impl<ValType: DAMType, StopType: DAMType> $trait for Token<ValType, StopType>
+        where
+            ValType: $trait,
+        {
+            fn $method(self) -> Self {
+                match self {
+                    Token::Val(val) => Token::Val(val.$method()),
+                    _ => self,
+                }
+            }
+        }
+
+        impl<T: num::Float> $trait for T {
+            fn $method(self) -> Self {
+                ($float_impl)(self)
+            }
+        }
+    };
+}
+
+RegisterUnaryOp!(ALUAbsOp, Abs, abs, |x: T| num::Float::abs(x));
+RegisterUnaryOp!(ALUSqrtOp, Sqrt, sqrt, |x: T| num::Float::sqrt(x));
+RegisterUnaryOp!(ALURsqrtOp, Rsqrt, rsqrt, |x: T| num::Float::recip(
+    num::Float::sqrt(x)
+));
+RegisterUnaryOp!(ALURecipOp, Recip, recip, |x: T| num::Float::recip(x));
+RegisterUnaryOp!(ALULogOp, Log, log, |x: T| num::Float::ln(x));
+RegisterUnaryOp!(ALUTanhOp, Tanh, tanh, |x: T| num::Float::tanh(x));
+RegisterUnaryOp!(ALUSigmoidOp, Sigmoid, sigmoid, |x: T| {
+    num::Float::recip(T::one() + num::Float::exp(-x))
+});
+// Tanh approximation of GELU, as used by most transformer implementations
+RegisterUnaryOp!(ALUGeluOp, Gelu, gelu, |x: T| {
+    let cast = |c: f64| <T as num::NumCast>::from(c).unwrap();
+    let scale = num::Float::sqrt(cast(2.0 / std::f64::consts::PI));
+    let inner = scale * (x + cast(0.044715) * x * x * x);
+    cast(0.5) * x * (T::one() + num::Float::tanh(inner))
+});
+
+pub trait Relu {
+    fn relu(self) -> Self;
+}
+
+RegisterALUOp!(ALUReluOp, |(i0), ()| [i0.relu()], T: DAMType + Relu);
+
+impl<ValType: DAMType, StopType: DAMType> Relu for Token<ValType, StopType>
+where
+    ValType: Relu,
+{
+    fn relu(self) -> Self {
+        match self {
+            Token::Val(val) => Token::Val(val.relu()),
+            _ => self,
+        }
+    }
+}
+
+// Unlike the ops above, ReLU is also defined for integers
+impl<T: num::Zero + PartialOrd> Relu for T {
+    fn relu(self) -> Self {
+        if self < T::zero() {
+            T::zero()
+        } else {
+            self
+        }
+    }
+}
+
 impl<ValType: DAMType, StopType: DAMType> fmt::Debug for Token<ValType, StopType> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -167,3 +245,50 @@ impl<ValType: DAMType, StopType: DAMType> DAMType for Token<ValType, StopType> {
 impl StaticallySized for Repsiggen {
     const SIZE: usize = 2;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Abs, Gelu, Log, Recip, Relu, Rsqrt, Sigmoid, Sqrt, Tanh, Token};
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn unary_values_test() {
+        assert_close(Abs::abs(-2.5f64), 2.5);
+        assert_close(Sqrt::sqrt(16.0f64), 4.0);
+        assert_close(Rsqrt::rsqrt(16.0f64), 0.25);
+        assert_close(Recip::recip(-4.0f64), -0.25);
+        assert_close(Log::log(std::f64::consts::E), 1.0);
+        assert_close(Log::log(1.0f64), 0.0);
+        assert_close(Tanh::tanh(0.5f64), 0.46211715726000974);
+        assert_close(Sigmoid::sigmoid(0.0f64), 0.5);
+        assert_eq!(Relu::relu(-3i32), 0);
+        assert_eq!(Relu::relu(3i32), 3);
+    }
+
+    #[test]
+    fn gelu_test() {
+        // Reference values of 0.5x(1 + tanh(sqrt(2/pi)(x + 0.044715x^3)))
+        assert_close(Gelu::gelu(0.0f64), 0.0);
+        assert_close(Gelu::gelu(1.0f64), 0.8411919906082768);
+        assert_close(Gelu::gelu(-1.0f64), -0.15880800939172324);
+        assert_close(Gelu::gelu(2.0f64), 1.954597694087775);
+        assert!((Gelu::gelu(1.0f32) - 0.841_192).abs() < 1e-5);
+    }
+
+    #[test]
+    fn unary_token_test() {
+        // Only values are transformed, control tokens pass through
+        let tokens: [Token<f32, u32>; 4] =
+            [Token::Val(4.0), Token::Stop(1), Token::Empty, Token::Done];
+        let expected: [Token<f32, u32>; 4] =
+            [Token::Val(0.5), Token::Stop(1), Token::Empty, Token::Done];
+        assert_eq!(tokens.map(Rsqrt::rsqrt), expected);
+        assert_eq!(Relu::relu(Token::<f32, u32>::Val(-1.0)), Token::Val(0.0));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::config::rd_scanner::CompressedCrdRdScanConfig;
+use crate::config::rd_scanner::{CompressedCrdRdScanConfig, TileRdScanConfig};
 use dam::{context_tools::*, dam_macros::context_macro};
 
 use super::primitive::Token;
@@ -30,6 +30,10 @@ pub struct TileRdScan<ValType: Clone, StopType: Clone> {
     seg_arrs: Vec<Vec<ValType>>,
     crd_arrs: Vec<Vec<ValType>>,
     num_tiles: usize,
+    /// Input stop level that ends a tile; without one, every tile ends with its own Done
+    tile_stop: Option<StopType>,
+
+    timing_config: TileRdScanConfig,
 }
 
 impl<ValType: DAMType, StopType: DAMType> UncompressedCrdRdScan<ValType, StopType>
@@ -96,6 +100,8 @@ where
             seg_arrs,
             crd_arrs,
             num_tiles,
+            tile_stop: None,
+            timing_config: Default::default(),
             context_info: Default::default(),
         };
         (ucr.rd_scan_data.in_ref).attach_receiver(&ucr);
@@ -104,12 +110,23 @@ where
 
         ucr
     }
+
+    pub fn set_timings(&mut self, new_config: TileRdScanConfig) {
+        self.timing_config = new_config
+    }
+
+    /// Moves to the next tile after each input stop at `level` instead of after each Done,
+    /// so all tiles share a single Done-terminated ref stream.
+    pub fn set_tile_stop(&mut self, level: StopType) {
+        self.tile_stop = Some(level)
+    }
 }
 
 impl<ValType, StopType> Context for UncompressedCrdRdScan<ValType, StopType>
 where
     ValType: DAMType
-        + std::ops::AddAssign<u32>
+        + std::ops::AddAssign<ValType>
+        + num::One
         + std::ops::Mul<ValType, Output = ValType>
         + std::ops::Add<ValType, Output = ValType>
         + std::cmp::PartialOrd<ValType>,
@@ -147,7 +164,7 @@ where
                                     ),
                                 )
                                 .unwrap();
-                            crd_count += 1;
+                            crd_count += ValType::one();
                             self.time.incr_cycles(1);
                         }
                         let next_tkn = self.rd_scan_data.in_ref.peek_next(&self.time).unwrap();
@@ -232,24 +249,26 @@ where
 impl<ValType, StopType> Context for TileRdScan<ValType, StopType>
 where
     ValType: DAMType
-        + std::ops::AddAssign<u32>
+        + std::ops::AddAssign<ValType>
+        + num::One
         + std::ops::Mul<ValType, Output = ValType>
         + std::ops::Add<ValType, Output = ValType>
         + std::cmp::PartialOrd<ValType>,
     // usize: From<ValType>,
     ValType: TryInto<usize>,
     <ValType as TryInto<usize>>::Error: std::fmt::Debug,
-    StopType: DAMType + std::ops::Add<u32, Output = StopType>,
+    StopType: DAMType + std::ops::Add<u32, Output = StopType> + std::cmp::PartialEq,
 {
     fn init(&mut self) {}
 
     fn run(&mut self) {
-        let latency = 1;
-        let initiation_interval = 1;
-        dbg!(latency);
-        dbg!(initiation_interval);
+        let latency = self.timing_config.output_latency;
+        let initiation_interval = self.timing_config.sequential_interval;
         let mut tile: usize = 0;
+        let tile_stop = self.tile_stop.clone();
+        let ends_tile = |stop: &StopType| tile_stop.as_ref() == Some(stop);
         loop {
+            let mut next_tile = false;
             match self.rd_scan_data.in_ref.dequeue(&self.time) {
                 Ok(curr_ref) => match curr_ref.data {
                     Token::Val(val) => {
@@ -281,7 +300,7 @@ where
                                     ),
                                 )
                                 .unwrap();
-                            curr_addr += 1;
+                            curr_addr += ValType::one();
                             self.time.incr_cycles(initiation_interval);
                         }
                         let next_tkn = self.rd_scan_data.in_ref.peek_next(&self.time).unwrap();
@@ -291,6 +310,7 @@ where
                             }
                             Token::Stop(stop_tkn) => {
                                 self.rd_scan_data.in_ref.dequeue(&self.time).unwrap();
+                                next_tile = ends_tile(&stop_tkn);
                                 Token::Stop(stop_tkn + 1)
                             } // Token::Empty => {
 
@@ -314,6 +334,7 @@ where
                             .unwrap();
                     }
                     Token::Stop(token) => {
+                        next_tile = ends_tile(&token);
                         let curr_time = self.time.tick();
                         self.rd_scan_data
                             .out_crd
@@ -351,9 +372,11 @@ where
                             .unwrap();
 
                         tile += 1;
-                        if tile == self.num_tiles {
+                        if tile == self.num_tiles || tile_stop.is_some() {
                             return;
                         }
+                        self.time
+                            .incr_cycles(self.timing_config.tile_switch_latency);
                     }
                     Token::Empty => {
                         let channel_elem =
@@ -370,6 +393,11 @@ where
                 },
                 Err(_) => panic!("Error: rd_scan_data dequeue error"),
             }
+            if next_tile {
+                tile += 1;
+                self.time
+                    .incr_cycles(self.timing_config.tile_switch_latency);
+            }
             self.time.incr_cycles(initiation_interval);
         }
     }
@@ -378,7 +406,8 @@ where
 impl<ValType, StopType> Context for CompressedCrdRdScan<ValType, StopType>
 where
     ValType: DAMType
-        + std::ops::AddAssign<u32>
+        + std::ops::AddAssign<ValType>
+        + num::One
         + std::ops::Mul<ValType, Output = ValType>
         + std::ops::Add<ValType, Output = ValType>
         + std::cmp::PartialOrd<ValType>,
@@ -454,7 +483,7 @@ where
                                     ),
                                 )
                                 .unwrap();
-                            curr_addr += 1;
+                            curr_addr += ValType::one();
                             self.time
                                 .incr_cycles(self.timing_config.sequential_interval);
                         }
@@ -586,9 +615,11 @@ where
 mod tests {
     use std::time::Instant;
 
+    use dam::simulation::InitializationOptions;
     use dam::simulation::InitializationOptionsBuilder;
     use dam::simulation::ProgramBuilder;
     use dam::simulation::RunMode;
+    use dam::simulation::RunOptions;
     use dam::simulation::RunOptionsBuilder;
     use dam::utility_contexts::CheckerContext;
     use dam::utility_contexts::ConsumerContext;
@@ -599,6 +630,8 @@ mod tests {
 
     use super::CompressedCrdRdScan;
     use super::RdScanData;
+    use super::TileRdScan;
+    use crate::config::rd_scanner::TileRdScanConfig;
 
     #[test]
     fn crd_2d_maybe_token() {
@@ -748,6 +781,85 @@ mod tests {
         compressed_rd_scan_calibration(seg_arr, crd_arr, in_ref, 224);
     }
 
+    #[test]
+    fn tile_rd_scan_test() {
+        // Each Done switches to the next tile's arrays
+        let seg_arrs = vec![vec![0u32, 2], vec![0, 1, 3]];
+        let crd_arrs = vec![vec![1u32, 3], vec![0, 2, 4]];
+        let in_ref = || token_vec!(u32; u32; 0, "D", 0, 1, "S0", "D").into_iter();
+        let out_crd =
+            || token_vec!(u32; u32; 1, 3, "S0", "D", 0, "S0", 2, 4, "S1", "D").into_iter();
+        let out_ref =
+            || token_vec!(u32; u32; 0, 1, "S0", "D", 0, "S0", 1, 2, "S1", "D").into_iter();
+
+        let mut parent = ProgramBuilder::default();
+        let (ref_sender, ref_receiver) = parent.unbounded::<Token<u32, u32>>();
+        let (crd_sender, crd_receiver) = parent.unbounded::<Token<u32, u32>>();
+        let (in_ref_sender, in_ref_receiver) = parent.unbounded::<Token<u32, u32>>();
+        let data = RdScanData::<u32, u32> {
+            in_ref: in_ref_receiver,
+            out_ref: ref_sender,
+            out_crd: crd_sender,
+        };
+        let mut trs = TileRdScan::new(data, seg_arrs, crd_arrs, 2);
+        trs.set_timings(TileRdScanConfig {
+            tile_switch_latency: 4,
+            ..Default::default()
+        });
+        parent.add_child(GeneratorContext::new(in_ref, in_ref_sender));
+        parent.add_child(CheckerContext::new(out_crd, crd_receiver));
+        parent.add_child(CheckerContext::new(out_ref, ref_receiver));
+        parent.add_child(trs);
+
+        let executed = parent
+            .initialize(
+                InitializationOptionsBuilder::default()
+                    .run_flavor_inference(true)
+                    .build()
+                    .unwrap(),
+            )
+            .unwrap()
+            .run(
+                RunOptionsBuilder::default()
+                    .mode(RunMode::Simple)
+                    .build()
+                    .unwrap(),
+            );
+        dbg!(executed.elapsed_cycles());
+    }
+
+    #[test]
+    fn tile_rd_scan_stop_test() {
+        // A single Done for all tiles, each stop at level 0 switches to the next tile's arrays
+        let seg_arrs = vec![vec![0u32, 2], vec![0, 1, 3]];
+        let crd_arrs = vec![vec![1u32, 3], vec![0, 2, 4]];
+        let in_ref = || token_vec!(u32; u32; 0, "S0", 0, 1, "S1", "D").into_iter();
+        let out_crd = || token_vec!(u32; u32; 1, 3, "S1", 0, "S0", 2, 4, "S2", "D").into_iter();
+        let out_ref = || token_vec!(u32; u32; 0, 1, "S1", 0, "S0", 1, 2, "S2", "D").into_iter();
+
+        let mut parent = ProgramBuilder::default();
+        let (ref_sender, ref_receiver) = parent.unbounded::<Token<u32, u32>>();
+        let (crd_sender, crd_receiver) = parent.unbounded::<Token<u32, u32>>();
+        let (in_ref_sender, in_ref_receiver) = parent.unbounded::<Token<u32, u32>>();
+        let data = RdScanData::<u32, u32> {
+            in_ref: in_ref_receiver,
+            out_ref: ref_sender,
+            out_crd: crd_sender,
+        };
+        let mut trs = TileRdScan::new(data, seg_arrs, crd_arrs, 2);
+        trs.set_tile_stop(0);
+        parent.add_child(GeneratorContext::new(in_ref, in_ref_sender));
+        parent.add_child(CheckerContext::new(out_crd, crd_receiver));
+        parent.add_child(CheckerContext::new(out_ref, ref_receiver));
+        parent.add_child(trs);
+
+        let executed = parent
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+    }
+
     fn compressed_rd_scan_calibration<IRT>(
         seg_arr: Vec<u32>,
         crd_arr: Vec<u32>,
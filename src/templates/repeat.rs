@@ -222,6 +222,26 @@ mod tests {
         full_repeat_test(in_repsig_ref, in_ref, out_ref);
     }
 
+    #[test]
+    fn full_repeat_crd_2d_test() {
+        // Same fibers as full_repeat_2d_test, given as the coordinates instead of the refs
+        let in_ref = || token_vec!(u32; u32; 0, 1, 2, "S0", "D").into_iter();
+        let in_repsig_crd = || token_vec!(u32; u32; 0, 3, "S0", 1, "S0", 2, "S1", "D").into_iter();
+        let out_ref = || token_vec!(u32; u32; 0, 0, "S0", 1, "S0", 2, "S1", "D").into_iter();
+
+        full_repeat_test(in_repsig_crd, in_ref, out_ref);
+    }
+
+    #[test]
+    fn full_repeat_empty_ref_test() {
+        // Refs coming out of a union carry empty tokens, which still repeat
+        let in_ref = || token_vec!(u32; u32; 0, 1, "S0", "D").into_iter();
+        let in_repsig_ref = || token_vec!(u32; u32; 0, "N", "S0", 1, "S1", "D").into_iter();
+        let out_ref = || token_vec!(u32; u32; 0, 0, "S0", 1, "S1", "D").into_iter();
+
+        full_repeat_test(in_repsig_ref, in_ref, out_ref);
+    }
+
     fn full_repeat_test<IRT1, IRT2, ORT>(
         in_ref_sig: fn() -> IRT1,
         in_ref: fn() -> IRT2,
@@ -144,6 +144,135 @@ where
     }
 }
 
+/// Merges the lanes of a Scatter back into one stream in the original order, for a stream `depth`
+/// fiber levels below the scattered one. `control` is a copy of the scattered stream: it tells which
+/// lane each element went to, and which stop closes the fiber an element produced.
+#[context_macro]
+pub struct OrderedGather<ValType: Clone, CtrlType: Clone, StopType: Clone> {
+    control: Receiver<Token<CtrlType, StopType>>,
+    targets: Vec<Receiver<Token<ValType, StopType>>>,
+    merged: Sender<Token<ValType, StopType>>,
+    depth: u32,
+}
+
+impl<ValType, CtrlType, StopType> OrderedGather<ValType, CtrlType, StopType>
+where
+    ValType: DAMType,
+    CtrlType: DAMType,
+    StopType: DAMType,
+{
+    fn next(&self, lane: usize) -> Token<ValType, StopType> {
+        self.targets[lane].dequeue(&self.time).unwrap().data
+    }
+
+    fn emit(&mut self, tkn: Token<ValType, StopType>) {
+        let channel_elem = ChannelElement::new(self.time.tick() + 1, tkn);
+        self.merged.enqueue(&self.time, channel_elem).unwrap();
+        self.time.incr_cycles(1);
+    }
+}
+
+impl<ValType, CtrlType, StopType> Context for OrderedGather<ValType, CtrlType, StopType>
+where
+    ValType: DAMType,
+    CtrlType: DAMType,
+    StopType: DAMType + std::ops::Add<u32, Output = StopType> + std::cmp::PartialOrd<u32>,
+{
+    fn init(&mut self) {}
+
+    fn run(&mut self) {
+        let num_lanes = self.targets.len();
+        let mut lane = 0;
+        // Lanes that got an element of the current fiber of the control stream
+        let mut active = vec![false; num_lanes];
+        // The previous element's fiber is still open, its stop depends on what follows it
+        let mut open_fiber = false;
+        loop {
+            match self.control.dequeue(&self.time).unwrap().data {
+                Token::Val(_) => {
+                    if open_fiber {
+                        self.emit(Token::Stop(StopType::default() + (self.depth - 1)));
+                    }
+                    active[lane] = true;
+                    if self.depth == 0 {
+                        let tkn = self.next(lane);
+                        self.emit(tkn);
+                    } else {
+                        // The lane's own stop only says whether the element was last in that lane
+                        loop {
+                            match self.next(lane) {
+                                Token::Stop(stkn) if stkn >= self.depth - 1 => break,
+                                Token::Done => panic!("Lane {lane} ended in the middle of a fiber"),
+                                tkn => self.emit(tkn),
+                            }
+                        }
+                        open_fiber = true;
+                    }
+                    lane = (lane + 1) % num_lanes;
+                }
+                Token::Stop(stkn) => {
+                    // Same-level lanes close every fiber, deeper lanes only close the ones they got no element of
+                    for idx in 0..num_lanes {
+                        if self.depth == 0 || !active[idx] {
+                            let tkn = self.next(idx);
+                            assert!(
+                                matches!(tkn, Token::Stop(_)),
+                                "Lane {idx} is out of step with the control stream"
+                            );
+                        }
+                    }
+                    active.fill(false);
+                    open_fiber = false;
+                    self.emit(Token::Stop(stkn + self.depth));
+                }
+                Token::Done => {
+                    for idx in 0..num_lanes {
+                        let tkn = self.next(idx);
+                        assert!(
+                            matches!(tkn, Token::Done),
+                            "Lane {idx} is out of step with the control stream"
+                        );
+                    }
+                    if open_fiber {
+                        self.emit(Token::Stop(StopType::default() + (self.depth - 1)));
+                    }
+                    self.emit(Token::Done);
+                    return;
+                }
+                Token::Empty => panic!("Scatter doesn't split empty tokens"),
+            }
+        }
+    }
+}
+
+impl<ValType: DAMType, CtrlType: DAMType, StopType: DAMType>
+    OrderedGather<ValType, CtrlType, StopType>
+where
+    OrderedGather<ValType, CtrlType, StopType>: Context,
+{
+    pub fn new(
+        control: Receiver<Token<CtrlType, StopType>>,
+        merged: Sender<Token<ValType, StopType>>,
+        depth: u32,
+    ) -> Self {
+        let x = Self {
+            control,
+            targets: vec![],
+            merged,
+            depth,
+            context_info: Default::default(),
+        };
+        x.control.attach_receiver(&x);
+        x.merged.attach_sender(&x);
+        x
+    }
+
+    pub fn add_target(&mut self, target: Receiver<Token<ValType, StopType>>) {
+        target.attach_receiver(self);
+        self.targets.push(target);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use dam::{
@@ -153,7 +282,7 @@ mod tests {
 
     use crate::{templates::primitive::Token, token_vec};
 
-    use super::{Gather, Scatter};
+    use super::{Gather, OrderedGather, Scatter};
 
     #[test]
     fn scatter_2d_test() {
@@ -173,6 +302,23 @@ mod tests {
         scatter_test(in_ref2, out_crd2, out_ref2);
     }
 
+    #[test]
+    fn ordered_gather_test() {
+        // Vals of the scattered refs, which went to lanes 0, 1, 0 and 1
+        let control = || token_vec!(u32; u32; 0, 1, 2, "S0", 3, "S1", "D").into_iter();
+        let lane0 = || token_vec!(u32; u32; 10, 12, "S0", "S1", "D").into_iter();
+        let lane1 = || token_vec!(u32; u32; 11, "S0", 13, "S1", "D").into_iter();
+        let merged = || token_vec!(u32; u32; 10, 11, 12, "S0", 13, "S1", "D").into_iter();
+        ordered_gather_test_helper(control, vec![lane0, lane1], merged, 0);
+
+        // Fibers one level down: ref 2 has an empty fiber, and lane 0 gets nothing in the second fiber
+        let control = || token_vec!(u32; u32; 0, 1, 2, "S0", 0, "S1", "D").into_iter();
+        let lane0 = || token_vec!(u32; u32; 5, 6, "S0", "S1", "S2", "D").into_iter();
+        let lane1 = || token_vec!(u32; u32; 7, "S1", 8, "S2", "D").into_iter();
+        let merged = || token_vec!(u32; u32; 5, 6, "S0", 7, "S0", "S1", 8, "S2", "D").into_iter();
+        ordered_gather_test_helper(control, vec![lane0, lane1], merged, 1);
+    }
+
     //TODO(lrubens): Fix this test
     #[ignore]
     #[test]
@@ -262,4 +408,35 @@ mod tests {
             .run(RunOptions::default());
         dbg!(executed.elapsed_cycles());
     }
+
+    fn ordered_gather_test_helper<CRT, LRT, ORT>(
+        control: fn() -> CRT,
+        lanes: Vec<fn() -> LRT>,
+        merged: fn() -> ORT,
+        depth: u32,
+    ) where
+        CRT: Iterator<Item = Token<u32, u32>> + 'static,
+        LRT: Iterator<Item = Token<u32, u32>> + 'static,
+        ORT: Iterator<Item = Token<u32, u32>> + 'static,
+    {
+        let mut parent = ProgramBuilder::default();
+        let chan_size = 128;
+
+        let (control_sender, control_receiver) = parent.bounded::<Token<u32, u32>>(chan_size);
+        let (merged_sender, merged_receiver) = parent.bounded::<Token<u32, u32>>(chan_size);
+        let mut gat = OrderedGather::new(control_receiver, merged_sender, depth);
+        for lane in lanes {
+            let (lane_sender, lane_receiver) = parent.bounded::<Token<u32, u32>>(chan_size);
+            gat.add_target(lane_receiver);
+            parent.add_child(GeneratorContext::new(lane, lane_sender));
+        }
+        parent.add_child(GeneratorContext::new(control, control_sender));
+        parent.add_child(CheckerContext::new(merged, merged_receiver));
+        parent.add_child(gat);
+        let executed = parent
+            .initialize(InitializationOptions::default())
+            .unwrap()
+            .run(RunOptions::default());
+        dbg!(executed.elapsed_cycles());
+    }
 }
@@ -1,15 +1,13 @@
 use std::{
     marker::PhantomData,
-    ops::{Add, Mul, Sub},
-    str::FromStr,
+    ops::{Add, AddAssign, Mul, Sub},
 };
 
 use dam::types::{DAMType, StaticallySized};
-use itertools::Itertools;
 
-use ndarray::{
-    ArrayBase, ArrayD, CowArray, Dimension, IntoDimension, IxDyn, LinalgScalar, OwnedRepr,
-};
+use super::data_file::DataElement;
+
+use ndarray::{ArrayD, CowArray, Dimension, IntoDimension, Ix2, IxDyn, LinalgScalar};
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Tensor<'a, ValType: DAMType> {
@@ -34,78 +32,117 @@ impl<T: DAMType> PrimitiveType<T> {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockError {
+    NoBlockSize,
+    PartialBlock { values: usize, block_len: usize },
+}
+
+impl std::fmt::Display for BlockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockError::NoBlockSize => write!(f, "blocked values need a nonzero block size"),
+            BlockError::PartialBlock { values, block_len } => write!(
+                f,
+                "{values} values don't divide into whole blocks of {block_len} ({} left over)",
+                values % block_len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BlockError {}
+
+/// Groups the scalars of a vals file into the values an Array streams out.
 pub trait Adapter<T> {
+    type Element: DataElement;
+
     fn parse(
         &self,
-        iter: std::iter::Flatten<std::io::Lines<std::io::BufReader<std::fs::File>>>,
+        vals: Vec<Self::Element>,
         size: Option<usize>,
         blocked: Option<bool>,
-    ) -> Vec<T>;
+    ) -> Result<Vec<T>, BlockError>;
 }
 
-impl<T: std::str::FromStr> Adapter<T> for PrimitiveType<T>
-where
-    T: DAMType,
-{
+impl<T: DataElement> Adapter<T> for PrimitiveType<T> {
+    type Element = T;
+
     fn parse(
         &self,
-        iter: std::iter::Flatten<std::io::Lines<std::io::BufReader<std::fs::File>>>,
+        vals: Vec<T>,
         _size: Option<usize>,
         _blocked: Option<bool>,
-    ) -> Vec<T> {
-        iter.flat_map(|line| line.parse::<T>()) // ignores Err variant from Result of str.parse
-            .collect()
+    ) -> Result<Vec<T>, BlockError> {
+        Ok(vals)
     }
 }
 
+/// Cuts the values into `size x size` blocks when `blocked`, or into vectors of `size` otherwise.
 impl<'a, A> Adapter<Tensor<'a, A>> for PrimitiveType<Tensor<'a, A>>
 where
-    A: DAMType + FromStr,
+    A: DataElement,
     Tensor<'a, A>: DAMType,
 {
+    type Element = A;
+
     fn parse(
         &self,
-        iter: std::iter::Flatten<std::io::Lines<std::io::BufReader<std::fs::File>>>,
+        vals: Vec<A>,
         size: Option<usize>,
         blocked: Option<bool>,
-    ) -> Vec<Tensor<'a, A>> {
-        let mut out_vec = vec![];
-        let float_iter = iter.flat_map(|line| line.parse::<A>());
-
-        for chunk in &float_iter.chunks(size.unwrap()) {
-            let arr = if blocked.unwrap() {
-                ArrayD::from_shape_vec(
-                    IxDyn(&[size.unwrap(), size.unwrap()]),
-                    chunk.into_iter().collect::<Vec<_>>(),
-                )
-            } else {
-                ArrayD::from_shape_vec(
-                    IxDyn(&[size.unwrap()]),
-                    chunk.into_iter().collect::<Vec<_>>(),
-                )
-            };
-            out_vec.push(Tensor::<'a, A> {
-                data: Some(CowArray::<'a, A, IxDyn>::from(arr.unwrap())),
+    ) -> Result<Vec<Tensor<'a, A>>, BlockError> {
+        let size = size
+            .filter(|size| *size > 0)
+            .ok_or(BlockError::NoBlockSize)?;
+        let shape = if blocked.unwrap_or(false) {
+            vec![size, size]
+        } else {
+            vec![size]
+        };
+        let block_len: usize = shape.iter().product();
+        if vals.len() % block_len != 0 {
+            return Err(BlockError::PartialBlock {
+                values: vals.len(),
+                block_len,
             });
         }
-        out_vec
+        Ok(vals
+            .chunks(block_len)
+            .map(|chunk| {
+                Tensor::from(ArrayD::from_shape_vec(IxDyn(&shape), chunk.to_vec()).unwrap())
+            })
+            .collect())
     }
 }
 
+impl<'a, A: DAMType> From<ArrayD<A>> for Tensor<'a, A> {
+    fn from(arr: ArrayD<A>) -> Self {
+        Tensor::<'a, A> {
+            data: Some(CowArray::<'a, A, IxDyn>::from(arr)),
+        }
+    }
+}
+
+// A tensor without data stands in for a block that was never materialized, i.e. a block of zeros:
+// it is the identity of addition and annihilates multiplication.
+
+/// Multiplies 2D blocks as matrices (so block-sparse matmul composes), and anything else elementwise.
 impl<'a, A> Mul for Tensor<'a, A>
 where
-    A: DAMType,
-    CowArray<'a, A, IxDyn>: LinalgScalar,
+    A: DAMType + LinalgScalar,
 {
     type Output = Self;
 
     fn mul(self, rhs: Self) -> Self::Output {
-        Self {
-            data: Some(
-                self.data
-                    .unwrap()
-                    .mul(rhs.data.expect("Attempting to multiply with a None value")),
-            ),
+        match (self.data, rhs.data) {
+            (Some(lhs), Some(rhs)) if lhs.ndim() == 2 && rhs.ndim() == 2 => {
+                let lhs = lhs.into_dimensionality::<Ix2>().unwrap();
+                let rhs = rhs.into_dimensionality::<Ix2>().unwrap();
+                Tensor::from(lhs.dot(&rhs).into_dyn())
+            }
+            (Some(lhs), Some(rhs)) => Tensor::from(&lhs * &rhs),
+            _ => Self::default(),
         }
     }
 }
@@ -124,50 +161,65 @@ impl<'a, A: DAMType + std::cmp::PartialEq + PartialOrd> PartialOrd for Tensor<'a
 
 impl<'a, A> Sub for Tensor<'a, A>
 where
-    A: PartialEq
-        + std::fmt::Debug
-        + Clone
-        + Default
-        + Sync
-        + Send
-        + StaticallySized
-        + num::Zero
-        + ndarray::RawData,
-    CowArray<'a, A, IxDyn>: Sub<Output = CowArray<'a, A, IxDyn>>,
+    A: DAMType + LinalgScalar,
 {
     type Output = Self;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        Tensor::<'a, A> {
-            // data: self.data.sub(rhs.data),
-            data: Some(
-                self.data
-                    .unwrap()
-                    .sub(rhs.data.expect("Attempting to substract with a None value")),
-            ),
+        match (self.data, rhs.data) {
+            (Some(lhs), Some(rhs)) => Tensor::from(&lhs - &rhs),
+            (None, Some(rhs)) => Tensor::from(rhs.mapv(|x| A::zero() - x)),
+            (lhs, None) => Tensor::<'a, A> { data: lhs },
         }
     }
 }
 
 impl<'a, A> Add for Tensor<'a, A>
 where
-    A: PartialEq + std::fmt::Debug + Clone + Default + Sync + Send + StaticallySized + num::Zero,
-    &'a ArrayBase<OwnedRepr<A>, IxDyn>:
-        Add<&'a ArrayBase<OwnedRepr<A>, IxDyn>, Output = ArrayBase<OwnedRepr<A>, IxDyn>>,
-    CowArray<'a, A, IxDyn>: Add<Output = CowArray<'a, A, IxDyn>>,
+    A: DAMType + LinalgScalar,
 {
     type Output = Self;
+
     fn add(self, rhs: Self) -> Self::Output {
-        Tensor::<'a, A> {
-            data: Some(
-                self.data
-                    .unwrap()
-                    .add(rhs.data.expect("Attempting to substract with a None value")),
-            ),
+        match (self.data, rhs.data) {
+            (Some(lhs), Some(rhs)) => Tensor::from(&lhs + &rhs),
+            (lhs, rhs) => Tensor::<'a, A> { data: lhs.or(rhs) },
         }
     }
 }
 
+impl<'a, A> AddAssign for Tensor<'a, A>
+where
+    A: DAMType + LinalgScalar,
+{
+    fn add_assign(&mut self, rhs: Self) {
+        *self = std::mem::take(self) + rhs;
+    }
+}
+
+impl<'a, A> num::Zero for Tensor<'a, A>
+where
+    A: DAMType + LinalgScalar,
+{
+    fn zero() -> Self {
+        Self::default()
+    }
+
+    fn is_zero(&self) -> bool {
+        self.data.is_none()
+    }
+}
+
+impl<'a, A> num::One for Tensor<'a, A>
+where
+    A: DAMType + LinalgScalar,
+{
+    /// A 0-dimensional one, which broadcasts against a block of any shape.
+    fn one() -> Self {
+        Tensor::from(ArrayD::from_elem(IxDyn(&[]), A::one()))
+    }
+}
+
 impl<'a, A> DAMType for Tensor<'a, A>
 where
     A: DAMType + StaticallySized,
@@ -176,10 +228,7 @@ where
     fn dam_size(&self) -> usize {
         self.data
             .as_ref()
-            .expect("Attempting to retrieve None tensor")
-            .dim()
-            .into_dimension()
-            .size()
+            .map_or(0, |data| data.dim().into_dimension().size())
             * A::SIZE
     }
 }
@@ -206,3 +255,60 @@ where
         Tensor::<'a, A> { data: None }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ndarray::{ArrayD, IxDyn};
+    use num::{One, Zero};
+
+    use super::{Adapter, BlockError, PrimitiveType, Tensor};
+
+    #[test]
+    fn parse_blocks_test() {
+        let adapter = PrimitiveType::<Tensor<'static, f32>>::new();
+        let vals: Vec<f32> = (0..8).map(|x| x as f32).collect();
+
+        let blocks = adapter.parse(vals.clone(), Some(2), Some(true)).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(
+            blocks[1],
+            Tensor::from(ArrayD::from_shape_vec(IxDyn(&[2, 2]), vec![4.0, 5.0, 6.0, 7.0]).unwrap())
+        );
+
+        let vectors = adapter.parse(vals.clone(), Some(4), Some(false)).unwrap();
+        assert_eq!(vectors.len(), 2);
+        assert_eq!(vectors[0].data.as_ref().unwrap().shape(), &[4]);
+    }
+
+    #[test]
+    fn block_arithmetic_test() {
+        let block = Tensor::from(
+            ArrayD::from_shape_vec(IxDyn(&[2, 2]), vec![1f32, 2.0, 3.0, 4.0]).unwrap(),
+        );
+        let missing = Tensor::<'static, f32>::default();
+
+        assert_eq!(block.clone() * missing.clone(), missing);
+        assert_eq!(missing.clone() * block.clone(), missing);
+        assert_eq!(block.clone() + missing.clone(), block);
+        assert_eq!(Tensor::one() * block.clone(), block);
+        assert_eq!(block.clone() * Tensor::one(), block);
+        assert!(!Tensor::<'static, f32>::one().is_zero());
+    }
+
+    #[test]
+    fn parse_partial_block_test() {
+        let adapter = PrimitiveType::<Tensor<'static, f32>>::new();
+        let vals = vec![0f32; 10];
+        assert_eq!(
+            adapter.parse(vals.clone(), Some(2), Some(true)),
+            Err(BlockError::PartialBlock {
+                values: 10,
+                block_len: 4
+            })
+        );
+        assert_eq!(
+            adapter.parse(vals, None, Some(false)),
+            Err(BlockError::NoBlockSize)
+        );
+    }
+}
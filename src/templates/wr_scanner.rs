@@ -4,11 +4,57 @@ use dam::{context_tools::*, dam_macros::context_macro};
 
 use super::primitive::Token;
 
+/// Cycles at which a writer saw the outermost stops of its stream, and when it finished.
+/// When the root emits several batches, those outermost stops are the batch separators.
+#[derive(Debug, Clone)]
+pub struct BatchMarks<StopType> {
+    level: Option<StopType>,
+    stops: Vec<u64>,
+    done: u64,
+}
+
+impl<StopType> Default for BatchMarks<StopType> {
+    fn default() -> Self {
+        Self {
+            level: None,
+            stops: vec![],
+            done: 0,
+        }
+    }
+}
+
+impl<StopType: PartialOrd + Clone> BatchMarks<StopType> {
+    fn stop(&mut self, level: &StopType, time: u64) {
+        match &self.level {
+            Some(max) if level < max => (),
+            Some(max) if level == max => self.stops.push(time),
+            _ => {
+                self.level = Some(level.clone());
+                self.stops = vec![time];
+            }
+        }
+    }
+
+    /// The cycle at which each of `num_batches` batches finished in this stream.
+    pub fn batch_ends(&self, num_batches: usize) -> Vec<u64> {
+        let separators = if num_batches > 1 {
+            &self.stops[..]
+        } else {
+            &[]
+        };
+        (separators.iter().take(num_batches - 1).copied())
+            .chain(std::iter::repeat(self.done))
+            .take(num_batches)
+            .collect()
+    }
+}
+
 #[context_macro]
 pub struct CompressedWrScan<ValType: Clone, StopType: Clone> {
     pub input: Receiver<Token<ValType, StopType>>,
     pub seg_arr: Arc<Mutex<Vec<ValType>>>,
     pub crd_arr: Arc<Mutex<Vec<ValType>>>,
+    pub marks: Arc<Mutex<BatchMarks<StopType>>>,
 }
 
 impl<ValType: DAMType, StopType: DAMType> CompressedWrScan<ValType, StopType>
@@ -20,6 +66,7 @@ where
             input,
             seg_arr: Default::default(),
             crd_arr: Default::default(),
+            marks: Default::default(),
             context_info: Default::default(),
         };
         (cwr).input.attach_receiver(&cwr);
@@ -31,12 +78,12 @@ where
 impl<ValType, StopType> Context for CompressedWrScan<ValType, StopType>
 where
     ValType: DAMType
-        + std::ops::AddAssign<u32>
+        + num::One
         + std::ops::AddAssign<ValType>
         + std::ops::Mul<ValType, Output = ValType>
         + std::ops::Add<ValType, Output = ValType>
         + std::cmp::PartialOrd<ValType>,
-    StopType: DAMType + std::ops::Add<u32, Output = StopType>,
+    StopType: DAMType + std::ops::Add<u32, Output = StopType> + std::cmp::PartialOrd,
 {
     fn init(&mut self) {
         // default is 0
@@ -50,24 +97,31 @@ where
 
         let mut crd_arr = self.crd_arr.lock().unwrap();
         let mut seg_arr = self.seg_arr.lock().unwrap();
+        let mut marks = self.marks.lock().unwrap();
         loop {
             match self.input.dequeue(&self.time) {
                 Ok(curr_in) => match curr_in.data {
                     Token::Val(val) => {
                         crd_arr.push(val);
-                        curr_crd_cnt += 1;
+                        curr_crd_cnt += ValType::one();
                         end_fiber = false;
                     }
-                    Token::Stop(_) if !end_fiber => {
+                    Token::Stop(stkn) if !end_fiber => {
+                        marks.stop(&stkn, self.time.tick().time());
                         seg_arr.push(curr_crd_cnt.clone());
                         end_fiber = true;
                     }
-                    Token::Empty | Token::Stop(_) => {
+                    Token::Stop(stkn) => {
+                        marks.stop(&stkn, self.time.tick().time());
+                        continue;
+                    }
+                    Token::Empty => {
                         // TODO: Maybe needs to be processed too
 
                         continue;
                     }
                     Token::Done => {
+                        marks.done = self.time.tick().time();
                         return;
                     }
                 },
@@ -84,6 +138,7 @@ where
 pub struct ValsWrScan<ValType: Clone, StopType: Clone> {
     pub input: Receiver<Token<ValType, StopType>>,
     pub out_val: Arc<Mutex<Vec<ValType>>>,
+    pub marks: Arc<Mutex<BatchMarks<StopType>>>,
 }
 
 impl<ValType: DAMType, StopType: DAMType> ValsWrScan<ValType, StopType>
@@ -94,6 +149,7 @@ where
         let vals = ValsWrScan {
             input,
             out_val: Default::default(),
+            marks: Default::default(),
             context_info: Default::default(),
         };
         (vals.input).attach_receiver(&vals);
@@ -105,7 +161,7 @@ where
 impl<ValType, StopType> Context for ValsWrScan<ValType, StopType>
 where
     ValType: DAMType,
-    StopType: DAMType + std::ops::Add<u32, Output = StopType>,
+    StopType: DAMType + std::ops::Add<u32, Output = StopType> + std::cmp::PartialOrd,
 {
     fn init(&mut self) {}
 
@@ -113,13 +169,18 @@ where
         let latency = 1;
         let initiation_interval = 1;
         let mut locked = self.out_val.lock().unwrap();
+        let mut marks = self.marks.lock().unwrap();
         loop {
             match self.input.dequeue(&self.time) {
                 Ok(curr_in) => match curr_in.data {
                     Token::Val(val) => {
                         locked.push(val);
                     }
-                    Token::Empty | Token::Stop(_) => {
+                    Token::Stop(stkn) => {
+                        marks.stop(&stkn, self.time.tick().time());
+                        continue;
+                    }
+                    Token::Empty => {
                         continue;
                     }
                     Token::Done => break,
@@ -131,5 +192,23 @@ where
             self.time.incr_cycles(initiation_interval);
         }
         self.time.incr_cycles(latency);
+        marks.done = self.time.tick().time();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BatchMarks;
+
+    #[test]
+    fn batch_ends_test() {
+        // Two batches of a 2D stream: the S1 at cycle 5 separates them
+        let mut marks = BatchMarks::<u32>::default();
+        for (level, time) in [(0, 2), (1, 5), (0, 7), (0, 9)] {
+            marks.stop(&level, time);
+        }
+        marks.done = 10;
+        assert_eq!(marks.batch_ends(2), vec![5, 10]);
+        assert_eq!(marks.batch_ends(1), vec![10]);
     }
 }
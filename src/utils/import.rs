@@ -0,0 +1,409 @@
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::config::manifest::{DataDir, TensorFile};
+
+use super::SparseTree;
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Header {
+        path: PathBuf,
+        reason: String,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        reason: String,
+    },
+    BadOrder {
+        order: Vec<usize>,
+        rank: usize,
+    },
+    Duplicate {
+        path: PathBuf,
+        coords: Vec<usize>,
+    },
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::Io { path, source } => write!(f, "{path:?}: {source}"),
+            ImportError::Header { path, reason } => write!(f, "{path:?}: {reason}"),
+            ImportError::Parse { path, line, reason } => {
+                write!(f, "{path:?} line {line}: {reason}")
+            }
+            ImportError::BadOrder { order, rank } => write!(
+                f,
+                "mode order {order:?} isn't a permutation of the {rank} modes"
+            ),
+            ImportError::Duplicate { path, coords } => {
+                write!(f, "{path:?} lists {coords:?} more than once")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+/// A tensor read from a file, with its levels in the requested mode order.
+pub struct ImportedTensor<CT, VT> {
+    /// Size of each level
+    pub shape: Vec<usize>,
+    pub tree: SparseTree<CT, VT>,
+}
+
+impl<CT: Clone + Display, VT: Clone + Display> ImportedTensor<CT, VT> {
+    /// Writes the shape and compressed seg/crd/vals files of every level, named by the directory's manifest.
+    pub fn write(&self, data: &DataDir, tensor: &str) -> std::io::Result<()> {
+        fs::create_dir_all(&data.root)?;
+        write_lines(&data.path(tensor, TensorFile::Shape), &self.shape)?;
+        let csf = self.tree.to_csf_with_rank(self.shape.len());
+        for mode in 0..csf.rank() {
+            let path = |file| data.path(tensor, file);
+            write_lines(&path(TensorFile::Seg(mode as u64)), &csf.seg(mode))?;
+            write_lines(&path(TensorFile::Crd(mode as u64)), csf.crd(mode))?;
+        }
+        write_lines(&data.path(tensor, TensorFile::Vals), csf.vals())
+    }
+}
+
+fn write_lines<T: Display>(path: &Path, vals: &[T]) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for val in vals {
+        writeln!(writer, "{val}")?;
+    }
+    writer.flush()
+}
+
+/// Reads the lines of a file, skipping blank ones and those starting with `comment`.
+fn content_lines(
+    path: &Path,
+    comment: &str,
+) -> Result<impl Iterator<Item = (usize, String)>, ImportError> {
+    let file = File::open(path).map_err(|source| ImportError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let lines = BufReader::new(file).lines().map_while(Result::ok);
+    let comment = comment.to_string();
+    Ok((lines.enumerate())
+        .map(|(index, line)| (index + 1, line.trim().to_string()))
+        .filter(move |(_, line)| !line.is_empty() && !line.starts_with(&comment)))
+}
+
+fn parse_field<T: FromStr>(
+    path: &Path,
+    line: usize,
+    field: Option<&str>,
+) -> Result<T, ImportError> {
+    let parse_err = |reason: String| ImportError::Parse {
+        path: path.to_path_buf(),
+        line,
+        reason,
+    };
+    let field = field.ok_or_else(|| parse_err("too few fields".to_string()))?;
+    field
+        .parse()
+        .map_err(|_| parse_err(format!("can't parse {field:?}")))
+}
+
+/// Parses a 1-based index and checks it against the mode's size.
+fn parse_index(
+    path: &Path,
+    line: usize,
+    field: Option<&str>,
+    size: usize,
+) -> Result<usize, ImportError> {
+    let index: usize = parse_field(path, line, field)?;
+    if index == 0 || index > size {
+        return Err(ImportError::Parse {
+            path: path.to_path_buf(),
+            line,
+            reason: format!("index {index} is outside of 1..={size}"),
+        });
+    }
+    Ok(index - 1)
+}
+
+/// Permutes the entries into `order`, sorts them and builds the tree.
+fn build_tree<CT, VT>(
+    path: &Path,
+    shape: Vec<usize>,
+    mut entries: Vec<(Vec<usize>, VT)>,
+    order: Option<&[usize]>,
+) -> Result<ImportedTensor<CT, VT>, ImportError>
+where
+    CT: TryFrom<usize> + Clone + PartialEq,
+    VT: Clone,
+{
+    let rank = shape.len();
+    let order: Vec<usize> = order.map_or_else(|| (0..rank).collect(), <[usize]>::to_vec);
+    let mut sorted_order = order.clone();
+    sorted_order.sort_unstable();
+    if sorted_order != (0..rank).collect::<Vec<_>>() {
+        return Err(ImportError::BadOrder { order, rank });
+    }
+
+    entries
+        .iter_mut()
+        .for_each(|(crd, _)| *crd = order.iter().map(|&mode| crd[mode]).collect());
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    if let Some(pair) = entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(ImportError::Duplicate {
+            path: path.to_path_buf(),
+            coords: pair[0].0.clone(),
+        });
+    }
+
+    let shape: Vec<usize> = order.iter().map(|&mode| shape[mode]).collect();
+    let too_large = |size: usize| ImportError::Header {
+        path: path.to_path_buf(),
+        reason: format!("a mode of size {size} doesn't fit in the coordinate type"),
+    };
+    if let Some(&size) = shape.iter().find(|&&size| CT::try_from(size).is_err()) {
+        return Err(too_large(size));
+    }
+    let entries: Vec<(Vec<CT>, VT)> = (entries.into_iter())
+        .map(|(crd, val)| {
+            let crd = crd.into_iter().map(|c| CT::try_from(c).ok().unwrap());
+            (crd.collect(), val)
+        })
+        .collect();
+    Ok(ImportedTensor {
+        tree: SparseTree::from_sorted_coo(&entries, rank),
+        shape,
+    })
+}
+
+/// Reads a Matrix Market coordinate matrix. General, symmetric and pattern matrices are supported;
+/// symmetric ones are expanded to both triangles and pattern entries read as one.
+/// `order` lists the modes from the outermost level in, e.g. `[1, 0]` stores the matrix by column.
+pub fn read_matrix_market<CT, VT>(
+    path: &Path,
+    order: Option<&[usize]>,
+) -> Result<ImportedTensor<CT, VT>, ImportError>
+where
+    CT: TryFrom<usize> + Clone + PartialEq,
+    VT: FromStr + num::One + Clone,
+{
+    let header_err = |reason: String| ImportError::Header {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let file = File::open(path).map_err(|source| ImportError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut banner = String::new();
+    BufReader::new(file)
+        .read_line(&mut banner)
+        .map_err(|source| ImportError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    let banner: Vec<String> = banner.split_whitespace().map(str::to_lowercase).collect();
+    let [magic, object, format, field, symmetry] = &banner[..] else {
+        return Err(header_err("missing the %%MatrixMarket banner".to_string()));
+    };
+    if magic != "%%matrixmarket" || object != "matrix" {
+        return Err(header_err("missing the %%MatrixMarket banner".to_string()));
+    }
+    if format != "coordinate" {
+        return Err(header_err(format!("{format} matrices aren't supported")));
+    }
+    let pattern = match field.as_str() {
+        "real" | "integer" | "double" => false,
+        "pattern" => true,
+        other => return Err(header_err(format!("{other} values aren't supported"))),
+    };
+    let symmetric = match symmetry.as_str() {
+        "general" => false,
+        "symmetric" => true,
+        other => return Err(header_err(format!("{other} matrices aren't supported"))),
+    };
+
+    let mut lines = content_lines(path, "%")?;
+    let (size_line, sizes) = lines
+        .next()
+        .ok_or_else(|| header_err("missing the size line".to_string()))?;
+    let mut sizes = sizes.split_whitespace();
+    let rows: usize = parse_field(path, size_line, sizes.next())?;
+    let cols: usize = parse_field(path, size_line, sizes.next())?;
+    let nnz: usize = parse_field(path, size_line, sizes.next())?;
+
+    let mut entries = Vec::with_capacity(if symmetric { nnz * 2 } else { nnz });
+    let mut count = 0;
+    for (line, text) in lines {
+        let mut fields = text.split_whitespace();
+        let row = parse_index(path, line, fields.next(), rows)?;
+        let col = parse_index(path, line, fields.next(), cols)?;
+        let val: VT = if pattern {
+            VT::one()
+        } else {
+            parse_field(path, line, fields.next())?
+        };
+        if symmetric && row != col {
+            entries.push((vec![col, row], val.clone()));
+        }
+        entries.push((vec![row, col], val));
+        count += 1;
+    }
+    if count != nnz {
+        return Err(header_err(format!(
+            "the size line promises {nnz} entries, but the file holds {count}"
+        )));
+    }
+    build_tree(path, vec![rows, cols], entries, order)
+}
+
+/// Reads a FROSTT tensor: one `i j k ... value` line per nonzero with 1-based indices.
+/// The file has no header, so each mode's size is its largest index.
+pub fn read_frostt<CT, VT>(
+    path: &Path,
+    order: Option<&[usize]>,
+) -> Result<ImportedTensor<CT, VT>, ImportError>
+where
+    CT: TryFrom<usize> + Clone + PartialEq,
+    VT: FromStr + Clone,
+{
+    let mut shape: Vec<usize> = vec![];
+    let mut entries = vec![];
+    for (line, text) in content_lines(path, "#")? {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        let (val, indices) = fields.split_last().unwrap();
+        if shape.is_empty() {
+            shape = vec![0; indices.len()];
+        }
+        if indices.is_empty() || indices.len() != shape.len() {
+            return Err(ImportError::Parse {
+                path: path.to_path_buf(),
+                line,
+                reason: format!("expected {} indices and a value", shape.len().max(1)),
+            });
+        }
+        let crd = (indices.iter())
+            .map(|index| parse_index(path, line, Some(index), usize::MAX))
+            .collect::<Result<Vec<usize>, _>>()?;
+        shape
+            .iter_mut()
+            .zip(&crd)
+            .for_each(|(size, &c)| *size = (*size).max(c + 1));
+        entries.push((crd, parse_field(path, line, Some(val))?));
+    }
+    if shape.is_empty() {
+        return Err(ImportError::Header {
+            path: path.to_path_buf(),
+            reason: "no entries".to_string(),
+        });
+    }
+    build_tree(path, shape, entries, order)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{read_frostt, read_matrix_market, ImportError};
+    use crate::config::manifest::{DataDir, TensorFile};
+    use crate::utils::scratch::ScratchDir;
+
+    #[test]
+    fn matrix_market_test() {
+        let dir = ScratchDir::new("matrix_market");
+        let path = dir.write(
+            "symmetric.mtx",
+            "%%MatrixMarket matrix coordinate pattern symmetric\n\
+             % lower triangle only\n\
+             3 3 3\n\
+             1 1\n\
+             3 1\n\
+             3 2\n",
+        );
+        let imported = read_matrix_market::<u32, f32>(&path, None).unwrap();
+        assert_eq!(imported.shape, vec![3, 3]);
+        let coords: Vec<Vec<u32>> = (imported.tree.to_coo().into_iter())
+            .map(|(crd, _)| crd)
+            .collect();
+        assert_eq!(
+            coords,
+            vec![vec![0, 0], vec![0, 2], vec![1, 2], vec![2, 0], vec![2, 1]]
+        );
+
+        let path = dir.write(
+            "general.mtx",
+            "%%MatrixMarket matrix coordinate real general\n2 3 2\n1 3 1.5\n2 1 -2\n",
+        );
+        let imported = read_matrix_market::<u32, f32>(&path, Some(&[1, 0])).unwrap();
+        assert_eq!(imported.shape, vec![3, 2]);
+        assert_eq!(
+            imported.tree.to_coo(),
+            vec![(vec![0, 1], -2.0), (vec![2, 0], 1.5)]
+        );
+
+        let path = dir.write(
+            "out_of_range.mtx",
+            "%%MatrixMarket matrix coordinate real general\n2 2 1\n3 1 1.0\n",
+        );
+        assert!(matches!(
+            read_matrix_market::<u32, f32>(&path, None),
+            Err(ImportError::Parse { line: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn empty_matrix_test() {
+        let dir = ScratchDir::new("empty_matrix");
+        let path = dir.write(
+            "empty.mtx",
+            "%%MatrixMarket matrix coordinate real general\n2 3 0\n",
+        );
+        let imported = read_matrix_market::<u32, f32>(&path, None).unwrap();
+        assert!(imported.tree.to_coo().is_empty());
+
+        let data = DataDir::open(dir.path().join("out")).unwrap();
+        imported.write(&data, "B").unwrap();
+        let read = |file| fs::read_to_string(data.path("B", file)).unwrap();
+        assert_eq!(read(TensorFile::Shape), "2\n3\n");
+        assert_eq!(read(TensorFile::Seg(0)), "0\n0\n");
+        assert_eq!(read(TensorFile::Crd(0)), "");
+        assert_eq!(read(TensorFile::Seg(1)), "0\n");
+        assert_eq!(read(TensorFile::Crd(1)), "");
+        assert_eq!(read(TensorFile::Vals), "");
+    }
+
+    #[test]
+    fn frostt_test() {
+        let dir = ScratchDir::new("frostt");
+        let path = dir.write("tensor.tns", "# comment\n1 2 3 1.0\n2 1 1 2.0\n1 2 1 3.0\n");
+        let imported = read_frostt::<u32, f64>(&path, Some(&[2, 0, 1])).unwrap();
+        assert_eq!(imported.shape, vec![3, 2, 2]);
+        assert_eq!(imported.tree.compute_rank(), Some(3));
+        assert_eq!(
+            imported.tree.to_coo(),
+            vec![
+                (vec![0, 0, 1], 3.0),
+                (vec![0, 1, 0], 2.0),
+                (vec![2, 0, 1], 1.0)
+            ]
+        );
+
+        assert!(matches!(
+            read_frostt::<u32, f64>(&path, Some(&[0, 0, 1])),
+            Err(ImportError::BadOrder { .. })
+        ));
+        let path = dir.write("duplicate.tns", "1 1 1.0\n1 1 2.0\n");
+        assert!(matches!(
+            read_frostt::<u32, f64>(&path, None),
+            Err(ImportError::Duplicate { .. })
+        ));
+    }
+}
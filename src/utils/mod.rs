@@ -1,5 +1,9 @@
 use rand_distr::Distribution;
 
+pub mod import;
+#[cfg(test)]
+pub(crate) mod scratch;
+
 /// This stores the conceptual CSF format, which is generally easier to manipulate and reason about.
 pub enum SparseTree<CoordType, ValType> {
     Outer(Vec<(CoordType, SparseTree<CoordType, ValType>)>),
@@ -86,29 +90,24 @@ impl<CT: Clone, VT: Clone> SparseTree<CT, VT> {
     }
 
     pub fn to_csf(&self) -> CompressedSparseFiber<CT, VT> {
-        let mut csf = CompressedSparseFiber {
-            outer_levels: {
-                let num_outer_ranks = self
-                    .compute_rank()
-                    .expect("Attempted to convert an ill-formed tensor into CSF")
-                    - 1;
-                // Push in a level per rank
-                (0..num_outer_ranks)
-                    .map(|_| Level {
-                        ids: vec![],
-                        payload: vec![0],
-                    })
-                    .collect()
-            },
-            inner_level: Level {
-                ids: vec![],
-                payload: vec![],
-            },
-        };
+        let rank = self
+            .compute_rank()
+            .expect("Attempted to convert an ill-formed tensor into CSF");
+        let mut csf = CompressedSparseFiber::empty(rank);
         self.csf_helper(&mut csf, 0);
         csf
     }
 
+    /// Like `to_csf`, but an empty tree has no levels to read its rank from, so it is taken from `rank`.
+    pub fn to_csf_with_rank(&self, rank: usize) -> CompressedSparseFiber<CT, VT> {
+        if self.is_empty_top() {
+            return CompressedSparseFiber::empty(rank);
+        }
+        let csf = self.to_csf();
+        assert_eq!(csf.rank(), rank, "Tensor rank doesn't match");
+        csf
+    }
+
     fn csf_helper(&self, workspace: &mut CompressedSparseFiber<CT, VT>, depth: usize) {
         match self {
             SparseTree::Outer(subtrees) => {
@@ -138,6 +137,180 @@ impl<CT: Clone, VT: Clone> SparseTree<CT, VT> {
     }
 }
 
+impl<CT: Clone + PartialEq, VT: Clone> SparseTree<CT, VT> {
+    /// Builds a tree of the given rank from coordinates that are sorted and free of duplicates.
+    pub fn from_sorted_coo(entries: &[(Vec<CT>, VT)], rank: usize) -> Self {
+        assert!(rank > 0);
+        Self::sorted_helper(entries, 0, rank)
+    }
+
+    fn sorted_helper(entries: &[(Vec<CT>, VT)], depth: usize, rank: usize) -> Self {
+        if depth + 1 == rank {
+            return SparseTree::Inner(
+                entries
+                    .iter()
+                    .map(|(crd, val)| (crd[depth].clone(), val.clone()))
+                    .collect(),
+            );
+        }
+        let mut children = vec![];
+        let mut start = 0;
+        while start < entries.len() {
+            let coord = &entries[start].0[depth];
+            let len = entries[start..]
+                .iter()
+                .take_while(|(crd, _)| crd[depth] == *coord)
+                .count();
+            let subtree = Self::sorted_helper(&entries[start..start + len], depth + 1, rank);
+            children.push((coord.clone(), subtree));
+            start += len;
+        }
+        SparseTree::Outer(children)
+    }
+}
+
+/// What `SparseTree::from_coo` does with coordinates listed more than once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Duplicates {
+    Sum,
+    KeepFirst,
+    KeepLast,
+    Reject,
+}
+
+/// Why `SparseTree::from_coo` couldn't build a tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CooError<CT> {
+    /// A coordinate with a different number of modes than the tree's rank
+    Rank { coords: Vec<CT>, rank: usize },
+    /// A coordinate listed more than once, with `Duplicates::Reject`
+    Duplicate(Vec<CT>),
+}
+
+impl<CT: std::fmt::Debug> std::fmt::Display for CooError<CT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CooError::Rank { coords, rank } => {
+                write!(f, "{coords:?} doesn't have {rank} coordinates")
+            }
+            CooError::Duplicate(coords) => write!(f, "{coords:?} is listed more than once"),
+        }
+    }
+}
+
+impl<CT: std::fmt::Debug> std::error::Error for CooError<CT> {}
+
+impl<CT: Ord + Clone, VT: Clone + std::ops::AddAssign> SparseTree<CT, VT> {
+    /// Builds a tree of the given rank from coordinates in any order.
+    pub fn from_coo(
+        mut entries: Vec<(Vec<CT>, VT)>,
+        rank: usize,
+        duplicates: Duplicates,
+    ) -> Result<Self, CooError<CT>> {
+        if let Some((crd, _)) = entries.iter().find(|(crd, _)| crd.len() != rank) {
+            return Err(CooError::Rank {
+                coords: crd.clone(),
+                rank,
+            });
+        }
+        // Stable, so repeated coordinates stay in input order for KeepFirst/KeepLast
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut merged: Vec<(Vec<CT>, VT)> = Vec::with_capacity(entries.len());
+        for (crd, val) in entries {
+            match merged.last_mut() {
+                Some((prev, prev_val)) if *prev == crd => match duplicates {
+                    Duplicates::Sum => *prev_val += val,
+                    Duplicates::KeepFirst => {}
+                    Duplicates::KeepLast => *prev_val = val,
+                    Duplicates::Reject => return Err(CooError::Duplicate(crd)),
+                },
+                _ => merged.push((crd, val)),
+            }
+        }
+        Ok(Self::from_sorted_coo(&merged, rank))
+    }
+}
+
+impl<CT: Clone, VT: Clone> SparseTree<CT, VT> {
+    pub fn from_csf(csf: &CompressedSparseFiber<CT, VT>) -> Self {
+        Self::csf_tree_helper(csf, 0, 0..csf.crd(0).len())
+    }
+
+    fn csf_tree_helper(
+        csf: &CompressedSparseFiber<CT, VT>,
+        depth: usize,
+        range: std::ops::Range<usize>,
+    ) -> Self {
+        match csf.outer_levels.get(depth) {
+            Some(level) => SparseTree::Outer(
+                range
+                    .map(|i| {
+                        let children = level.payload[i]..level.payload[i + 1];
+                        (
+                            level.ids[i].clone(),
+                            Self::csf_tree_helper(csf, depth + 1, children),
+                        )
+                    })
+                    .collect(),
+            ),
+            None => SparseTree::Inner(
+                range
+                    .map(|i| {
+                        (
+                            csf.inner_level.ids[i].clone(),
+                            csf.inner_level.payload[i].clone(),
+                        )
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl<CT: TryInto<usize> + Clone, VT: Clone + num::Zero> SparseTree<CT, VT>
+where
+    <CT as TryInto<usize>>::Error: std::fmt::Debug,
+{
+    /// Fills a dense array of the given shape, leaving zeros where the tree has no entry.
+    pub fn to_dense(&self, shape: &[usize]) -> ndarray::ArrayD<VT> {
+        let mut dense = ndarray::ArrayD::zeros(ndarray::IxDyn(shape));
+        for (crd, val) in self.to_coo() {
+            let index: Vec<usize> = crd.into_iter().map(|c| c.try_into().unwrap()).collect();
+            dense[index.as_slice()] = val;
+        }
+        dense
+    }
+}
+
+impl<CT: TryFrom<usize> + Clone + PartialEq, VT> SparseTree<CT, VT>
+where
+    <CT as TryFrom<usize>>::Error: std::fmt::Debug,
+    VT: Clone + PartialOrd + num::Zero + std::ops::Sub<Output = VT>,
+{
+    /// Keeps the entries of `dense` whose magnitude is above `threshold`; pass zero to keep every nonzero.
+    /// If nothing is kept the tree is empty, so convert it with `to_csf_with_rank`.
+    pub fn from_dense(dense: &ndarray::ArrayD<VT>, threshold: VT) -> Self {
+        assert!(dense.ndim() > 0);
+        let zero = VT::zero();
+        let entries: Vec<(Vec<CT>, VT)> = dense
+            .indexed_iter()
+            .filter(|(_, val)| {
+                let magnitude = if **val < zero {
+                    zero.clone() - (*val).clone()
+                } else {
+                    (*val).clone()
+                };
+                magnitude > threshold
+            })
+            .map(|(index, val)| {
+                let crd = index.slice().iter().map(|&i| CT::try_from(i).unwrap());
+                (crd.collect(), val.clone())
+            })
+            .collect();
+        Self::from_sorted_coo(&entries, dense.ndim())
+    }
+}
+
 impl<CT: TryFrom<usize>, VT> SparseTree<CT, VT>
 where
     <CT as TryFrom<usize>>::Error: std::fmt::Debug,
@@ -203,6 +376,56 @@ pub struct CompressedSparseFiber<CoordType, ValType> {
     pub inner_level: Level<CoordType, ValType>,
 }
 
+impl<CT, VT> CompressedSparseFiber<CT, VT> {
+    /// A tensor of the given rank without any nonzeros.
+    pub fn empty(rank: usize) -> Self {
+        assert!(rank > 0);
+        CompressedSparseFiber {
+            // Push in a level per rank
+            outer_levels: (1..rank)
+                .map(|_| Level {
+                    ids: vec![],
+                    payload: vec![0],
+                })
+                .collect(),
+            inner_level: Level {
+                ids: vec![],
+                payload: vec![],
+            },
+        }
+    }
+
+    pub fn rank(&self) -> usize {
+        self.outer_levels.len() + 1
+    }
+
+    /// Segment array of a mode, in the layout of the `tensor_*_mode_*_seg` files.
+    pub fn seg(&self, mode: usize) -> Vec<usize> {
+        match mode {
+            0 => {
+                let len = match self.outer_levels.first() {
+                    Some(level) => level.ids.len(),
+                    None => self.inner_level.ids.len(),
+                };
+                vec![0, len]
+            }
+            _ => self.outer_levels[mode - 1].payload.clone(),
+        }
+    }
+
+    /// Coordinate array of a mode, in the layout of the `tensor_*_mode_*_crd` files.
+    pub fn crd(&self, mode: usize) -> &[CT] {
+        match self.outer_levels.get(mode) {
+            Some(level) => &level.ids,
+            None => &self.inner_level.ids,
+        }
+    }
+
+    pub fn vals(&self) -> &[VT] {
+        &self.inner_level.payload
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct Level<IDType, PayloadType> {
     pub ids: Vec<IDType>,
@@ -213,7 +436,7 @@ pub struct Level<IDType, PayloadType> {
 mod tests {
     use crate::utils::{CompressedSparseFiber, Level};
 
-    use super::SparseTree;
+    use super::{CooError, Duplicates, SparseTree};
 
     const EXAMPLE_TREE: fn() -> SparseTree<i32, f64> = || {
         SparseTree::Outer(vec![
@@ -289,6 +512,81 @@ mod tests {
             },
         };
         assert_eq!(csf, gold);
+        assert_eq!(csf.seg(0), vec![0, 2]);
+        assert_eq!(csf.crd(3), &[2, 3, 1, 3, 1, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_sparsetree_from_sorted_coo() {
+        let coo = EXAMPLE_TREE().to_coo();
+        let st = SparseTree::from_sorted_coo(&coo, 4);
+        assert_eq!(st.to_csf(), EXAMPLE_TREE().to_csf());
+    }
+
+    #[test]
+    fn test_sparsetree_from_coo() {
+        let mut coo = EXAMPLE_TREE().to_coo();
+        coo.reverse();
+        coo.push((vec![1, 2, 1, 3], 10.0));
+        let gold = |dup_val: f64| {
+            let mut gold = EXAMPLE_TREE().to_coo();
+            gold[3].1 = dup_val;
+            gold
+        };
+
+        let sum = SparseTree::from_coo(coo.clone(), 4, Duplicates::Sum).unwrap();
+        assert_eq!(sum.to_coo(), gold(14.0));
+        let first = SparseTree::from_coo(coo.clone(), 4, Duplicates::KeepFirst).unwrap();
+        assert_eq!(first.to_coo(), gold(4.0));
+        let last = SparseTree::from_coo(coo.clone(), 4, Duplicates::KeepLast).unwrap();
+        assert_eq!(last.to_coo(), gold(10.0));
+        assert_eq!(
+            SparseTree::from_coo(coo.clone(), 4, Duplicates::Reject).err(),
+            Some(CooError::Duplicate(vec![1, 2, 1, 3]))
+        );
+        assert_eq!(
+            SparseTree::from_coo(coo, 3, Duplicates::Sum).err(),
+            Some(CooError::Rank {
+                coords: vec![2, 2, 2, 3],
+                rank: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_sparsetree_from_csf() {
+        let csf = EXAMPLE_TREE().to_csf();
+        let st = SparseTree::from_csf(&csf);
+        assert_eq!(st.to_coo(), EXAMPLE_TREE().to_coo());
+        assert_eq!(st.to_csf(), csf);
+    }
+
+    #[test]
+    fn test_sparsetree_dense() {
+        let dense = EXAMPLE_TREE().to_dense(&[3, 3, 3, 4]);
+        assert_eq!(dense[[1, 2, 1, 3]], 4.0);
+        assert_eq!(dense.iter().filter(|val| **val != 0.0).count(), 8);
+
+        let st = SparseTree::<i32, f64>::from_dense(&dense, 0.0);
+        assert_eq!(st.to_coo(), EXAMPLE_TREE().to_coo());
+
+        let mut noisy = dense.clone();
+        noisy[[0, 0, 0, 0]] = 1e-9;
+        noisy[[0, 1, 0, 0]] = -1e-9;
+        let st = SparseTree::<i32, f64>::from_dense(&noisy, 1e-6);
+        assert_eq!(st.to_coo(), EXAMPLE_TREE().to_coo());
+
+        // Nothing is above the threshold, so the tree is empty but keeps its rank through CSF
+        let st = SparseTree::<i32, f64>::from_dense(&noisy, 100.0);
+        assert!(st.to_coo().is_empty());
+        let csf = st.to_csf_with_rank(4);
+        assert_eq!(csf, CompressedSparseFiber::empty(4));
+        assert_eq!(csf.seg(0), vec![0, 0]);
+        assert_eq!(csf.seg(3), vec![0]);
+        assert_eq!(
+            st.to_dense(&[3, 3, 3, 4]),
+            ndarray::ArrayD::zeros(noisy.shape())
+        );
     }
 
     #[test]
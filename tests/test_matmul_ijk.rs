@@ -44,7 +44,6 @@ fn test_matmul_ijk() {
     let b0_crd = read_inputs::<u32>(&b0_crd_filename);
     let b1_seg = read_inputs::<u32>(&b1_seg_filename);
     let b1_crd = read_inputs::<u32>(&b1_crd_filename);
-    // let b_vals = read_inputs_vectorized(&b_vals_filename, PrimitiveType::<VT>::new());
     let b_vals = read_inputs(&b_vals_filename);
     let c0_seg = read_inputs::<u32>(&c0_seg_filename);
     let c0_crd = read_inputs::<u32>(&c0_crd_filename);